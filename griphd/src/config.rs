@@ -0,0 +1,104 @@
+use std::sync::LazyLock;
+
+use crate::metrics::DEFAULT_METRICS_ADDR;
+
+pub const DEFAULT_BACKEND_URL: &str = "http://localhost:8080/api/v1/secret/flags";
+
+// griphd takes its settings from the environment only:
+// GRIPHD_BACKEND_URL - where send_flag posts the token,
+// GRIPHD_METRICS_ADDR - /metrics and /status address, "off" disables them,
+// RUST_LOG, GRIPHD_LOG_JSON=1, GRIPHD_REDACT_PAYLOAD=1 - see logging
+#[derive(Debug)]
+pub struct Config {
+    pub backend_url: String,
+    pub metrics_addr: String,
+    pub log_filter: String,
+    pub log_json: bool,
+    pub redact_payload: bool,
+}
+
+pub static CONFIG: LazyLock<Config> = LazyLock::new(Config::from_env);
+
+impl Config {
+    pub fn from_env() -> Self {
+        Config {
+            backend_url: env_or("GRIPHD_BACKEND_URL", DEFAULT_BACKEND_URL),
+            metrics_addr: env_or("GRIPHD_METRICS_ADDR", DEFAULT_METRICS_ADDR),
+            log_filter: env_or("RUST_LOG", "info"),
+            log_json: env_flag("GRIPHD_LOG_JSON"),
+            redact_payload: env_flag("GRIPHD_REDACT_PAYLOAD"),
+        }
+    }
+
+    // CRC32 over the settings that change what griphd does with a payload.
+    // Logging and the metrics address are left out: `griphd status` compares
+    // the daemon's checksum with its own environment, which is a shell, not
+    // the unit
+    pub fn checksum(&self) -> u32 {
+        let text = format!(
+            "backend_url={}\nredact_payload={}\n",
+            self.backend_url, self.redact_payload
+        );
+        crc32fast::hash(text.as_bytes())
+    }
+
+    pub fn metrics_enabled(&self) -> bool {
+        self.metrics_addr != "off"
+    }
+}
+
+fn env_or(name: &str, default: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| default.to_string())
+}
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| value == "1" || value == "true")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            backend_url: DEFAULT_BACKEND_URL.to_string(),
+            metrics_addr: DEFAULT_METRICS_ADDR.to_string(),
+            log_filter: "info".to_string(),
+            log_json: false,
+            redact_payload: false,
+        }
+    }
+
+    #[test]
+    fn checksum_follows_payload_settings_only() {
+        let base = config().checksum();
+        assert_eq!(config().checksum(), base);
+        let changed = [
+            Config {
+                backend_url: "http://backend:8080/flags".to_string(),
+                ..config()
+            },
+            Config {
+                redact_payload: true,
+                ..config()
+            },
+        ];
+        for config in changed {
+            assert_ne!(config.checksum(), base);
+        }
+        let logging = Config {
+            log_filter: "debug".to_string(),
+            log_json: true,
+            metrics_addr: "127.0.0.1:9101".to_string(),
+            ..config()
+        };
+        assert_eq!(logging.checksum(), base);
+
+        assert!(config().metrics_enabled());
+        let off = Config {
+            metrics_addr: "off".to_string(),
+            ..config()
+        };
+        assert!(!off.metrics_enabled());
+    }
+}
@@ -1 +1,8 @@
+pub mod config;
+pub mod logging;
+pub mod metrics;
+pub mod notify;
+pub mod outcome;
+pub mod status;
 pub mod vm;
+pub mod watch;
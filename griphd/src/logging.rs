@@ -0,0 +1,59 @@
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing_subscriber::EnvFilter;
+
+use crate::config::CONFIG;
+
+// Hide payload bytes and the decoded program, log only their size
+static REDACT: AtomicBool = AtomicBool::new(false);
+
+// Settings come from the environment, see config
+pub fn init() {
+    REDACT.store(CONFIG.redact_payload, Ordering::Relaxed);
+    let filter = EnvFilter::try_new(&CONFIG.log_filter).unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(std::io::stdout().is_terminal());
+    if CONFIG.log_json {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+}
+
+pub fn redacting() -> bool {
+    REDACT.load(Ordering::Relaxed)
+}
+
+// Bytes as hex, or only their length when redacting
+pub fn bytes(data: &[u8]) -> Bytes<'_> {
+    Bytes(data)
+}
+
+pub struct Bytes<'a>(&'a [u8]);
+
+impl fmt::Display for Bytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if redacting() {
+            return write!(f, "<{} bytes redacted>", self.0.len());
+        }
+        for byte in self.0 {
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_are_hex_unless_redacted() {
+        assert_eq!(bytes(&[0x0A, 0xFF]).to_string(), "0AFF");
+        REDACT.store(true, Ordering::Relaxed);
+        assert_eq!(bytes(&[0x0A, 0xFF]).to_string(), "<2 bytes redacted>");
+        REDACT.store(false, Ordering::Relaxed);
+    }
+}
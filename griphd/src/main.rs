@@ -1,12 +1,46 @@
+use griphd::logging;
+use griphd::metrics::{self, METRICS};
+use griphd::notify::{self, Heartbeat};
+use griphd::outcome;
+use griphd::status;
 use griphd::vm::core;
+use griphd::watch::PayloadWatcher;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const INPUT: &str = "/tmp/rfid_input.bin";
+const INPUT_POLL: Duration = Duration::from_millis(200);
+// The VM run itself is instant; the rest is the flag request's 30 s timeout
+const PAYLOAD_TIME_LIMIT: Duration = Duration::from_secs(60);
 
 fn main() {
-    let input = match std::fs::read("/tmp/rfid_input.bin") {
-        Ok(i) => i,
-        Err(_) => return,
-    };
-    println!("Input: {:?}", input);
-    core::handle_payload(&input);
+    // `griphd status` asks the running daemon; the exit code is for systemd
+    if std::env::args().nth(1).as_deref() == Some("status") {
+        std::process::exit(status::query());
+    }
+    logging::init();
+    metrics::serve();
     let addr = core::send_flag as *const ();
-    println!("{:#x}", addr as usize);
+    info!(send_flag = format_args!("{:#x}", addr as usize));
+
+    if let Err(e) = notify::notify("READY=1") {
+        warn!(error = %e, "cannot notify systemd");
+    }
+    let mut heartbeat = Heartbeat::from_env();
+    let mut watcher = PayloadWatcher::new(INPUT);
+    loop {
+        if let Err(e) = heartbeat.beat() {
+            warn!(error = %e, "cannot ping systemd watchdog");
+        }
+        if let Some(input) = watcher.poll() {
+            debug!(input = %logging::bytes(&input), "payload read");
+            METRICS.payloads.inc();
+            let timer = METRICS.payload_seconds.start_timer();
+            let result = heartbeat.during(PAYLOAD_TIME_LIMIT, || core::handle_payload(&input));
+            timer.observe_duration();
+            status::payload_handled(input.len(), result);
+            outcome::report(result);
+        }
+        std::thread::sleep(INPUT_POLL);
+    }
 }
@@ -0,0 +1,161 @@
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use std::thread;
+use tracing::{debug, info, warn};
+
+use crate::config::CONFIG;
+use crate::status;
+
+// GRIPHD_METRICS_ADDR overrides it, see config
+pub const DEFAULT_METRICS_ADDR: &str = "127.0.0.1:9101";
+
+pub struct Metrics {
+    registry: Registry,
+    pub payloads: IntCounter,
+    // reason: too_short, bad_type, crc
+    pub payloads_rejected: IntCounterVec,
+    pub vm_runs: IntCounter,
+    // JEQ reg0 == 3826 taken, CALL is allowed from here on
+    pub gate_unlocks: IntCounter,
+    // result: ok, error
+    pub flag_deliveries: IntCounterVec,
+    pub payload_seconds: Histogram,
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+impl Metrics {
+    fn new() -> Self {
+        let metrics = Metrics {
+            registry: Registry::new(),
+            payloads: IntCounter::new("griphd_payloads_total", "Payloads read from the card")
+                .unwrap(),
+            payloads_rejected: IntCounterVec::new(
+                Opts::new(
+                    "griphd_payloads_rejected_total",
+                    "Payloads rejected by reason",
+                ),
+                &["reason"],
+            )
+            .unwrap(),
+            vm_runs: IntCounter::new("griphd_vm_runs_total", "Programs run by the VM").unwrap(),
+            gate_unlocks: IntCounter::new(
+                "griphd_gate_unlocks_total",
+                "Programs that opened the CALL gate",
+            )
+            .unwrap(),
+            flag_deliveries: IntCounterVec::new(
+                Opts::new("griphd_flag_deliveries_total", "Flag submissions by result"),
+                &["result"],
+            )
+            .unwrap(),
+            payload_seconds: Histogram::with_opts(
+                HistogramOpts::new("griphd_payload_seconds", "Time to handle one payload").buckets(
+                    vec![0.001, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0],
+                ),
+            )
+            .unwrap(),
+        };
+        // Pre-create label values so the series show up at zero
+        for reason in ["too_short", "bad_type", "crc"] {
+            metrics.payloads_rejected.with_label_values(&[reason]);
+        }
+        for result in ["ok", "error"] {
+            metrics.flag_deliveries.with_label_values(&[result]);
+        }
+        let collectors: [Box<dyn prometheus::core::Collector>; 6] = [
+            Box::new(metrics.payloads.clone()),
+            Box::new(metrics.payloads_rejected.clone()),
+            Box::new(metrics.vm_runs.clone()),
+            Box::new(metrics.gate_unlocks.clone()),
+            Box::new(metrics.flag_deliveries.clone()),
+            Box::new(metrics.payload_seconds.clone()),
+        ];
+        for collector in collectors {
+            metrics.registry.register(collector).unwrap();
+        }
+        metrics
+    }
+
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+// Serves GET /metrics and /status from a background thread. A busy port only
+// costs us the endpoint, never the payload handling.
+pub fn serve() {
+    if !CONFIG.metrics_enabled() {
+        return;
+    }
+    let addr = &CONFIG.metrics_addr;
+    match start(addr) {
+        Ok(_) => {
+            info!(%addr, "serving metrics");
+            status::start_backend_probe();
+        }
+        Err(e) => warn!(%addr, error = %e, "cannot serve metrics"),
+    }
+}
+
+fn start(addr: &str) -> Result<SocketAddr, Box<dyn Error + Send + Sync>> {
+    let server = tiny_http::Server::http(addr)?;
+    let local = server
+        .server_addr()
+        .to_ip()
+        .ok_or("metrics address is not an IP address")?;
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            debug!(url = request.url(), "metrics request");
+            let response = if request.url() == "/metrics" {
+                let header =
+                    tiny_http::Header::from_bytes("Content-Type", TextEncoder::new().format_type())
+                        .unwrap();
+                tiny_http::Response::from_string(METRICS.encode()).with_header(header)
+            } else if request.url() == "/status" {
+                let (healthy, body) = status::report();
+                let header =
+                    tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap();
+                tiny_http::Response::from_string(body.to_string())
+                    .with_header(header)
+                    .with_status_code(if healthy { 200 } else { 503 })
+            } else {
+                tiny_http::Response::from_string("not found").with_status_code(404)
+            };
+            if let Err(e) = request.respond(response) {
+                warn!(error = %e, "cannot answer metrics request");
+            }
+        }
+    });
+    Ok(local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_rejections_and_serves_them() {
+        METRICS.payloads_rejected.with_label_values(&["crc"]).inc();
+        let addr = start("127.0.0.1:0").unwrap();
+        let get = |path: &str| reqwest::blocking::get(format!("http://{}{}", addr, path)).unwrap();
+
+        let metrics = get("/metrics");
+        assert_eq!(metrics.status(), 200);
+        let text = metrics.text().unwrap();
+        assert!(text.contains("griphd_payloads_rejected_total{reason=\"crc\"} 1"));
+        assert!(text.contains("griphd_flag_deliveries_total{result=\"error\"} 0"));
+
+        let status: serde_json::Value = get("/status").json().unwrap();
+        assert!(status["config_checksum"].is_string());
+        assert_eq!(get("/other").status(), 404);
+    }
+}
@@ -0,0 +1,146 @@
+use std::env;
+use std::io;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// sd_notify: sends `state` (READY=1, WATCHDOG=1, ...) to $NOTIFY_SOCKET.
+// Returns Ok(false) when not started by systemd.
+pub fn notify(state: &str) -> io::Result<bool> {
+    let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let path = path.to_string_lossy().into_owned();
+    // A leading @ is the Linux abstract namespace
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes())?,
+        None => SocketAddr::from_pathname(&path)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(true)
+}
+
+// WATCHDOG_USEC, unless WATCHDOG_PID says it is meant for another process
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = env::var("WATCHDOG_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        && pid != std::process::id()
+    {
+        return None;
+    }
+    let usec: u64 = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+// WATCHDOG=1 from the main loop, at most every half watchdog interval.
+// A VM program stuck in a JEQ loop stops the pings and systemd restarts us.
+pub struct Heartbeat {
+    period: Option<Duration>,
+    last: Option<Instant>,
+}
+
+impl Heartbeat {
+    pub fn from_env() -> Self {
+        Heartbeat {
+            period: watchdog_interval().map(|interval| interval / 2),
+            last: None,
+        }
+    }
+
+    pub fn beat(&mut self) -> io::Result<()> {
+        let Some(period) = self.period else {
+            return Ok(());
+        };
+        if self.last.is_some_and(|last| last.elapsed() < period) {
+            return Ok(());
+        }
+        notify("WATCHDOG=1")?;
+        self.last = Some(Instant::now());
+        Ok(())
+    }
+
+    // Runs `work` while a helper thread keeps pinging, but for no longer than
+    // `limit`: a payload may legitimately wait on the backend for longer than
+    // the watchdog interval, one still running after `limit` is a stuck program.
+    pub fn during<T>(&mut self, limit: Duration, work: impl FnOnce() -> T) -> T {
+        let Some(period) = self.period else {
+            return work();
+        };
+        let started = Instant::now();
+        let (done, finished) = mpsc::channel::<()>();
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(period) {
+                    if started.elapsed() >= limit {
+                        warn!(?limit, "payload still running, letting the watchdog fire");
+                        break;
+                    }
+                    if let Err(e) = notify("WATCHDOG=1") {
+                        warn!(error = %e, "cannot ping systemd watchdog");
+                    }
+                }
+            });
+            let result = work();
+            drop(done);
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_state_and_rate_limits_watchdog() {
+        let path = env::temp_dir().join(format!("griphd_notify_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        systemd
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        // SAFETY: no other test reads NOTIFY_SOCKET
+        unsafe { env::set_var("NOTIFY_SOCKET", &path) };
+
+        assert!(notify("READY=1").unwrap());
+        let mut buf = [0u8; 32];
+        let len = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+
+        let mut heartbeat = Heartbeat {
+            period: Some(Duration::from_secs(60)),
+            last: None,
+        };
+        heartbeat.beat().unwrap();
+        heartbeat.beat().unwrap();
+        let len = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"WATCHDOG=1");
+        systemd.set_nonblocking(true).unwrap();
+        assert!(systemd.recv(&mut buf).is_err());
+        systemd.set_nonblocking(false).unwrap();
+
+        // Pings keep coming while the work runs, until the limit
+        let mut heartbeat = Heartbeat {
+            period: Some(Duration::from_millis(50)),
+            last: None,
+        };
+        let result = heartbeat.during(Duration::from_millis(180), || {
+            thread::sleep(Duration::from_millis(400));
+            7
+        });
+        assert_eq!(result, 7);
+        systemd.set_nonblocking(true).unwrap();
+        let mut pings = 0;
+        while systemd.recv(&mut buf).is_ok() {
+            pings += 1;
+        }
+        assert!((2..=4).contains(&pings), "{} pings", pings);
+
+        unsafe { env::remove_var("NOTIFY_SOCKET") };
+        std::fs::remove_file(&path).unwrap();
+    }
+}
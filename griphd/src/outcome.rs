@@ -0,0 +1,14 @@
+use nfc_format::outcome::{OUTCOME_SOCKET, Outcome};
+use std::os::unix::net::UnixDatagram;
+use tracing::{debug, info};
+
+// Tells nfc_reader how the payload ended so the booth can light up.
+// Nobody listening (no reader, no feedback configured) is not an error.
+pub fn report(outcome: Outcome) {
+    info!(outcome = outcome.as_str(), "payload handled");
+    let sent = UnixDatagram::unbound()
+        .and_then(|socket| socket.send_to(outcome.as_str().as_bytes(), OUTCOME_SOCKET));
+    if let Err(e) = sent {
+        debug!(error = %e, "outcome not delivered");
+    }
+}
@@ -0,0 +1,194 @@
+use nfc_format::outcome::Outcome;
+use serde_json::{Value, json};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+use crate::config::{CONFIG, Config};
+
+const BACKEND_TIMEOUT: Duration = Duration::from_secs(2);
+const BACKEND_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+// A probe older than this means the probe thread is stuck; report unhealthy
+const BACKEND_PROBE_MAX_AGE: Duration = Duration::from_secs(30);
+
+struct LastPayload {
+    at: SystemTime,
+    len: usize,
+    outcome: Outcome,
+}
+
+static LAST_PAYLOAD: Mutex<Option<LastPayload>> = Mutex::new(None);
+
+struct BackendProbe {
+    at: SystemTime,
+    reachable: bool,
+}
+
+static BACKEND: Mutex<Option<BackendProbe>> = Mutex::new(None);
+
+pub fn payload_handled(len: usize, outcome: Outcome) {
+    *LAST_PAYLOAD.lock().unwrap() = Some(LastPayload {
+        at: SystemTime::now(),
+        len,
+        outcome,
+    });
+}
+
+// Any HTTP answer counts, even 404/405: we only want to know the flag
+// backend is up. HEAD so that nothing gets submitted.
+pub fn backend_reachable(url: &str) -> bool {
+    let client = match reqwest::blocking::Client::builder()
+        .timeout(BACKEND_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(_) => return false,
+    };
+    match client.head(url).send() {
+        Ok(_) => true,
+        Err(e) => {
+            debug!(error = %e, "backend unreachable");
+            false
+        }
+    }
+}
+
+fn backend_probed(reachable: bool) {
+    *BACKEND.lock().unwrap() = Some(BackendProbe {
+        at: SystemTime::now(),
+        reachable,
+    });
+}
+
+// /status is answered on the metrics thread, so it must not wait for the
+// backend: a probe thread checks it every 10 s and report() uses the result
+pub fn start_backend_probe() {
+    thread::spawn(|| {
+        loop {
+            backend_probed(backend_reachable(&CONFIG.backend_url));
+            thread::sleep(BACKEND_PROBE_INTERVAL);
+        }
+    });
+}
+
+// Body of /status; healthy means the last backend probe, no older than
+// BACKEND_PROBE_MAX_AGE, got an answer
+pub fn report() -> (bool, Value) {
+    let probe = BACKEND.lock().unwrap();
+    let backend = probe.as_ref().filter(|probe| {
+        probe
+            .at
+            .elapsed()
+            .is_ok_and(|age| age <= BACKEND_PROBE_MAX_AGE)
+    });
+    let healthy = backend.is_some_and(|probe| probe.reachable);
+    let last = LAST_PAYLOAD.lock().unwrap();
+    let body = json!({
+        "healthy": healthy,
+        "backend_url": CONFIG.backend_url,
+        "backend_reachable": backend.map(|probe| probe.reachable),
+        "backend_checked_at": probe.as_ref().map(|probe| {
+            probe.at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+        }),
+        "config_checksum": format!("{:08x}", CONFIG.checksum()),
+        "last_payload": last.as_ref().map(|last| json!({
+            "at": last.at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            "len": last.len,
+            "outcome": last.outcome.as_str(),
+        })),
+    });
+    (healthy, body)
+}
+
+// `griphd status`: asks the running daemon, prints its answer and returns the
+// exit code: 0 healthy, 1 backend down, 2 daemon not answering. A config
+// checksum that differs from this shell's environment is only a warning: the
+// shell need not match the unit
+pub fn query() -> i32 {
+    if !CONFIG.metrics_enabled() {
+        eprintln!("GRIPHD_METRICS_ADDR=off, griphd has no status endpoint");
+        return 2;
+    }
+    let url = format!("http://{}/status", CONFIG.metrics_addr);
+    let response = reqwest::blocking::Client::builder()
+        .timeout(BACKEND_TIMEOUT * 3)
+        .build()
+        .and_then(|client| client.get(&url).send());
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("griphd is not answering on {}: {}", CONFIG.metrics_addr, e);
+            return 2;
+        }
+    };
+    let healthy = response.status().is_success();
+    let body: Value = match response.json() {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("bad status answer: {}", e);
+            return 2;
+        }
+    };
+    println!("{}", body);
+    let ours = format!("{:08x}", Config::from_env().checksum());
+    if body["config_checksum"] != ours.as_str() {
+        eprintln!(
+            "warning: config checksum {} differs from this environment ({}), \
+             restart griphd if its unit was changed",
+            body["config_checksum"], ours
+        );
+    }
+    if healthy { 0 } else { 1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn reports_the_cached_backend_probe() {
+        let (healthy, body) = report();
+        assert!(!healthy);
+        assert_eq!(body["backend_reachable"], Value::Null);
+        assert_eq!(body["last_payload"], Value::Null);
+
+        backend_probed(true);
+        payload_handled(42, Outcome::PayloadRejected);
+        let (healthy, body) = report();
+        assert!(healthy);
+        assert_eq!(body["backend_reachable"], true);
+        assert_eq!(body["last_payload"]["len"], 42);
+        assert_eq!(body["last_payload"]["outcome"], "payload-rejected");
+        assert_eq!(
+            body["config_checksum"],
+            format!("{:08x}", CONFIG.checksum())
+        );
+
+        // A stuck probe thread must not keep reporting the old answer
+        BACKEND.lock().unwrap().as_mut().unwrap().at -= BACKEND_PROBE_MAX_AGE * 2;
+        let (healthy, body) = report();
+        assert!(!healthy);
+        assert_eq!(body["backend_reachable"], Value::Null);
+    }
+
+    #[test]
+    fn any_http_answer_means_reachable() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/flags", server.server_addr().to_ip().unwrap());
+        thread::spawn(move || {
+            let request = server.recv().unwrap();
+            assert_eq!(request.method(), &tiny_http::Method::Head);
+            request.respond(tiny_http::Response::empty(405)).unwrap();
+        });
+        assert!(backend_reachable(&url));
+
+        // A port nobody listens on
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        assert!(!backend_reachable(&format!("http://{}/flags", closed)));
+    }
+}
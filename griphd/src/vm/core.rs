@@ -1,7 +1,13 @@
+use nfc_format::outcome::Outcome;
 use reqwest;
 use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{debug, error, info, warn};
 
 use super::consts::{REG_COUNT, XOR_KEY};
+use crate::config::CONFIG;
+use crate::logging;
+use crate::metrics::METRICS;
 
 type Reg = usize;
 
@@ -13,47 +19,76 @@ enum Instruction {
     Call { addr: usize },
     Nop,
 }
+// Set by send_flag: the VM reaches it through a raw CALL, not a return value
+static FLAG_SENT: AtomicBool = AtomicBool::new(false);
+
 // payload: [tag][encrypted payload][CRC] -> [1b][1b][4b]
-pub fn handle_payload(input: &[u8]) {
+pub fn handle_payload(input: &[u8]) -> Outcome {
     if input.len() < 5 {
-        println!("Payload too short");
-        return;
+        warn!(len = input.len(), "payload too short");
+        METRICS
+            .payloads_rejected
+            .with_label_values(&["too_short"])
+            .inc();
+        return Outcome::PayloadRejected;
     }
 
     let tag: u8 = input[0];
     //type of payload: 0x03 -> VM Payload
     if tag != 0x03 {
-        println!("Invalid payload type: {:?}", tag);
-        return;
+        warn!(tag, "invalid payload type");
+        METRICS
+            .payloads_rejected
+            .with_label_values(&["bad_type"])
+            .inc();
+        return Outcome::PayloadRejected;
     }
     // Get encrypted body
     let encrypted = &input[1..];
     if encrypted.len() < 5 {
-        println!("Encrypted body too short");
-        return;
+        warn!(len = encrypted.len(), "encrypted body too short");
+        METRICS
+            .payloads_rejected
+            .with_label_values(&["too_short"])
+            .inc();
+        return Outcome::PayloadRejected;
     }
     // Get encrypted payload
     let e_payload: &[u8] = &encrypted[..encrypted.len() - 4];
-    println!("Encrypted payload: {:?}", e_payload);
+    debug!(payload = %logging::bytes(e_payload), "encrypted payload");
     // Get crc
     let crc_body: &[u8] = &encrypted[encrypted.len() - 4..];
-    println!("CRC body: {:?}", crc_body);
 
     let d_payload: Vec<u8> = e_payload.iter().map(|b| b ^ XOR_KEY).collect();
-    println!("Decrypted payload: {:?}", d_payload);
+    debug!(payload = %logging::bytes(&d_payload), "decrypted payload");
     let checksum_payload = crc32fast::hash(&d_payload);
-    println!("Calculated checksum: {:?}", checksum_payload);
     let expected_checksum = u32::from_le_bytes(crc_body.try_into().unwrap());
-    println!("Expected checksum: {:?}", expected_checksum);
+    debug!(
+        calculated = checksum_payload,
+        expected = expected_checksum,
+        "checksum"
+    );
 
     if checksum_payload != expected_checksum {
-        println!("Checksum mismatch");
-        return;
+        warn!("checksum mismatch");
+        METRICS.payloads_rejected.with_label_values(&["crc"]).inc();
+        return Outcome::PayloadRejected;
     }
-    println!("Payload decrypted successfully");
+    info!("payload decrypted successfully");
     let program = parse_program(&d_payload);
-    println!("Program length: {:?}; Program {:?}", program.len(), program);
+    if logging::redacting() {
+        debug!(instructions = program.len(), "program parsed");
+    } else {
+        debug!(instructions = program.len(), ?program, "program parsed");
+    }
+    FLAG_SENT.store(false, Ordering::SeqCst);
+    METRICS.vm_runs.inc();
     run_vm(program);
+    if FLAG_SENT.load(Ordering::SeqCst) {
+        Outcome::FlagSent
+    } else {
+        Outcome::Executed
+    }
 }
 
 fn parse_program(bytes: &[u8]) -> Vec<Instruction> {
@@ -154,7 +189,8 @@ fn run_vm(instraction: Vec<Instruction>) {
             }
             Instruction::Jeq { reg, cmp, jmp } => {
                 if *reg < REG_COUNT && registers[*reg] == *cmp {
-                    if *reg == 0 && *cmp == 3826 {
+                    if *reg == 0 && *cmp == 3826 && !vm_allowed_to_call {
+                        METRICS.gate_unlocks.inc();
                         vm_allowed_to_call = true;
                     }
                     program_count = *jmp;
@@ -179,15 +215,25 @@ fn run_vm(instraction: Vec<Instruction>) {
 pub fn send_flag() {
     let client = reqwest::blocking::Client::new();
     let flag = client
-        .post("http://localhost:8080/api/v1/secret/flags") // need env or args
+        .post(&CONFIG.backend_url)
         .json(&json!({"token": "CTF{super_ctf_mastermind}"}))
         .send();
 
     match flag {
         Ok(resp) => match resp.text() {
-            Ok(text) => println!("YOU ARE CODE: {}", text),
-            Err(e) => eprintln!("ERR READ INFO: {}", e),
+            Ok(text) => {
+                FLAG_SENT.store(true, Ordering::SeqCst);
+                METRICS.flag_deliveries.with_label_values(&["ok"]).inc();
+                info!(response = %text, "flag sent")
+            }
+            Err(e) => {
+                METRICS.flag_deliveries.with_label_values(&["error"]).inc();
+                error!(error = %e, "cannot read flag response")
+            }
         },
-        Err(e) => eprintln!("ERR SEND TOKEN: {}", e),
+        Err(e) => {
+            METRICS.flag_deliveries.with_label_values(&["error"]).inc();
+            error!(error = %e, "cannot send token")
+        }
     }
 }
@@ -0,0 +1,75 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Polls the payload file that nfc_reader replaces for every card: a changed
+// mtime is a new payload.
+pub struct PayloadWatcher {
+    path: PathBuf,
+    last_seen: Option<SystemTime>,
+}
+
+impl PayloadWatcher {
+    // A payload already in place at startup was handled by the previous run
+    // (or predates a reboot). Running it again after a crash or a watchdog
+    // kill would only kill us again, so it counts as seen.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let last_seen = modified(&path);
+        PayloadWatcher { path, last_seen }
+    }
+
+    // The payload, if the file changed since the last call
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        let modified = modified(&self.path)?;
+        if self.last_seen == Some(modified) {
+            return None;
+        }
+        self.last_seen = Some(modified);
+        std::fs::read(&self.path).ok()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn replace(path: &Path, data: &[u8], modified: SystemTime) {
+        std::fs::write(path, data).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    fn skips_the_payload_left_from_before_startup() {
+        let path = std::env::temp_dir().join(format!("griphd_watch_{}.bin", std::process::id()));
+        let start = SystemTime::now();
+        replace(&path, b"old", start);
+
+        let mut watcher = PayloadWatcher::new(&path);
+        assert_eq!(watcher.poll(), None);
+
+        replace(&path, b"new", start + Duration::from_secs(1));
+        assert_eq!(watcher.poll().as_deref(), Some(&b"new"[..]));
+        assert_eq!(watcher.poll(), None);
+
+        // No file at startup: the first payload is a new one
+        std::fs::remove_file(&path).unwrap();
+        let mut watcher = PayloadWatcher::new(&path);
+        assert_eq!(watcher.poll(), None);
+        replace(&path, b"first", start);
+        assert_eq!(watcher.poll().as_deref(), Some(&b"first"[..]));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
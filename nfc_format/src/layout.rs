@@ -0,0 +1,151 @@
+use std::fmt;
+
+/// Собственная разметка payload на карте – проще NDEF, когда телефон не нужен.
+///
+/// Лежит в области данных карты (у MIFARE Classic – блоки с сектора 1 без
+/// трейлеров, у NTAG – страницы с 4-й). Первый блок – заголовок:
+/// | magic "GRPH" (4) | version (1) | flags (1) | length BE (2) | reserved (8) |
+/// дальше `length` байт payload, добитые нулями до целого блока.
+pub const LAYOUT_MAGIC: [u8; 4] = *b"GRPH";
+pub const LAYOUT_VERSION: u8 = 1;
+/// Заголовок занимает ровно один блок MIFARE Classic.
+pub const HEADER_LEN: usize = 16;
+pub const BLOCK_SIZE: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    /// Область данных короче, чем заявлено в заголовке.
+    Truncated {
+        needed: usize,
+        got: usize,
+    },
+    BadMagic,
+    UnsupportedVersion(u8),
+    /// Payload не помещается в 16-битное поле длины.
+    TooLong(usize),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::Truncated { needed, got } => {
+                write!(f, "card data truncated: need {} bytes, got {}", needed, got)
+            }
+            LayoutError::BadMagic => write!(f, "no GRPH header"),
+            LayoutError::UnsupportedVersion(v) => write!(f, "unsupported layout version {}", v),
+            LayoutError::TooLong(len) => write!(f, "payload of {} bytes is too long", len),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub flags: u8,
+    pub length: usize,
+}
+
+impl Header {
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut header = [0u8; HEADER_LEN];
+        header[..4].copy_from_slice(&LAYOUT_MAGIC);
+        header[4] = self.version;
+        header[5] = self.flags;
+        header[6..8].copy_from_slice(&(self.length as u16).to_be_bytes());
+        header
+    }
+
+    pub fn parse(area: &[u8]) -> Result<Self, LayoutError> {
+        if area.len() < HEADER_LEN {
+            return Err(LayoutError::Truncated {
+                needed: HEADER_LEN,
+                got: area.len(),
+            });
+        }
+        if area[..4] != LAYOUT_MAGIC {
+            return Err(LayoutError::BadMagic);
+        }
+        if area[4] != LAYOUT_VERSION {
+            return Err(LayoutError::UnsupportedVersion(area[4]));
+        }
+        Ok(Header {
+            version: area[4],
+            flags: area[5],
+            length: u16::from_be_bytes([area[6], area[7]]) as usize,
+        })
+    }
+}
+
+/// Начинается ли область данных с заголовка GRPH.
+pub fn is_layout(area: &[u8]) -> bool {
+    area.starts_with(&LAYOUT_MAGIC)
+}
+
+/// Заголовок + payload, добитые нулями до целого числа блоков.
+pub fn encode(payload: &[u8]) -> Result<Vec<u8>, LayoutError> {
+    if payload.len() > u16::MAX as usize {
+        return Err(LayoutError::TooLong(payload.len()));
+    }
+    let header = Header {
+        version: LAYOUT_VERSION,
+        flags: 0,
+        length: payload.len(),
+    };
+    let mut area = header.to_bytes().to_vec();
+    area.extend_from_slice(payload);
+    area.resize(area.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+    Ok(area)
+}
+
+/// Достаёт payload из области данных карты.
+pub fn decode(area: &[u8]) -> Result<Vec<u8>, LayoutError> {
+    let header = Header::parse(area)?;
+    let end = HEADER_LEN + header.length;
+    if area.len() < end {
+        return Err(LayoutError::Truncated {
+            needed: end,
+            got: area.len(),
+        });
+    }
+    Ok(area[HEADER_LEN..end].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_pads_to_blocks() {
+        let payload: Vec<u8> = (0..40).collect();
+        let area = encode(&payload).unwrap();
+        assert_eq!(area.len(), 64);
+        assert_eq!(&area[..8], b"GRPH\x01\x00\x00\x28");
+        assert!(is_layout(&area));
+        assert_eq!(decode(&area).unwrap(), payload);
+
+        // Хвост после payload (остальные блоки карты) не мешает
+        let mut card = area.clone();
+        card.extend_from_slice(&[0xAA; 32]);
+        assert_eq!(decode(&card).unwrap(), payload);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let area = encode(b"payload").unwrap();
+        assert_eq!(
+            decode(&area[..20]),
+            Err(LayoutError::Truncated {
+                needed: 23,
+                got: 20
+            })
+        );
+        assert_eq!(decode(&[0x30; 32]), Err(LayoutError::BadMagic));
+
+        let mut future = area.clone();
+        future[4] = 2;
+        assert_eq!(decode(&future), Err(LayoutError::UnsupportedVersion(2)));
+        assert_eq!(encode(&vec![0; 70_000]), Err(LayoutError::TooLong(70_000)));
+    }
+}
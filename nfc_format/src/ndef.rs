@@ -0,0 +1,694 @@
+use std::fmt;
+
+/// MIME-тип записи, в которой лежит payload для VM.
+pub const CTF_MIME_TYPE: &str = "application/x-ctf";
+
+// Флаги заголовка NDEF-записи
+// | MB | ME | CF | SR | IL | TNF (3 бита) |
+const FLAG_MB: u8 = 0x80;
+const FLAG_ME: u8 = 0x40;
+const FLAG_CF: u8 = 0x20;
+const FLAG_SR: u8 = 0x10;
+const FLAG_IL: u8 = 0x08;
+const TNF_MASK: u8 = 0x07;
+/// Длина языка в байте статуса текстовой записи (биты 0-5).
+const MAX_LANG_LEN: usize = 0x3F;
+
+// TLV-блоки в области данных метки (NFC Forum Type 1/2, MIFARE Classic)
+pub const TLV_NULL: u8 = 0x00;
+pub const TLV_LOCK_CONTROL: u8 = 0x01;
+pub const TLV_MEMORY_CONTROL: u8 = 0x02;
+pub const TLV_NDEF_MESSAGE: u8 = 0x03;
+pub const TLV_PROPRIETARY: u8 = 0xFD;
+pub const TLV_TERMINATOR: u8 = 0xFE;
+
+/// Сокращения префиксов для URI-записей (NFC Forum URI RTD, таблица 3).
+const URI_PREFIXES: [&str; 36] = [
+    "",
+    "http://www.",
+    "https://www.",
+    "http://",
+    "https://",
+    "tel:",
+    "mailto:",
+    "ftp://anonymous:anonymous@",
+    "ftp://ftp.",
+    "ftps://",
+    "sftp://",
+    "smb://",
+    "nfs://",
+    "ftp://",
+    "dav://",
+    "news:",
+    "telnet://",
+    "imap:",
+    "rtsp://",
+    "urn:",
+    "pop:",
+    "sip:",
+    "sips:",
+    "tftp:",
+    "btspp://",
+    "btl2cap://",
+    "btgoep://",
+    "tcpobex://",
+    "irdaobex://",
+    "file://",
+    "urn:epc:id:",
+    "urn:epc:tag:",
+    "urn:epc:pat:",
+    "urn:epc:raw:",
+    "urn:epc:",
+    "urn:nfc:",
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum NdefError {
+    /// Данные закончились раньше, чем описано в заголовке.
+    Truncated,
+    /// В области данных нет NDEF TLV.
+    NoMessage,
+    /// Первая запись без MB, лишние записи после ME и т.п.
+    InvalidMessage(&'static str),
+    /// Некорректный TNF или длина типа для данного TNF.
+    InvalidRecord(&'static str),
+    /// В сообщении нет записи нужного типа.
+    RecordNotFound(String),
+    /// Сообщение не помещается в TLV: длина больше 0xFFFE.
+    TooLong(usize),
+    /// Поле записи длиннее, чем позволяет его поле длины.
+    FieldTooLong {
+        field: &'static str,
+        len: usize,
+        max: usize,
+    },
+}
+
+impl fmt::Display for NdefError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NdefError::Truncated => write!(f, "NDEF data is truncated"),
+            NdefError::NoMessage => write!(f, "no NDEF message TLV found"),
+            NdefError::InvalidMessage(msg) => write!(f, "invalid NDEF message: {}", msg),
+            NdefError::InvalidRecord(msg) => write!(f, "invalid NDEF record: {}", msg),
+            NdefError::RecordNotFound(t) => write!(f, "no NDEF record of type {}", t),
+            NdefError::TooLong(len) => {
+                write!(f, "NDEF message of {} bytes does not fit in a TLV", len)
+            }
+            NdefError::FieldTooLong { field, len, max } => write!(
+                f,
+                "NDEF record {} of {} bytes is longer than {}",
+                field, len, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NdefError {}
+
+/// Type Name Format – как интерпретировать поле type записи.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tnf {
+    Empty = 0x00,
+    WellKnown = 0x01,
+    Mime = 0x02,
+    AbsoluteUri = 0x03,
+    External = 0x04,
+    Unknown = 0x05,
+    Unchanged = 0x06,
+}
+
+impl Tnf {
+    fn from_bits(bits: u8) -> Result<Self, NdefError> {
+        match bits & TNF_MASK {
+            0x00 => Ok(Tnf::Empty),
+            0x01 => Ok(Tnf::WellKnown),
+            0x02 => Ok(Tnf::Mime),
+            0x03 => Ok(Tnf::AbsoluteUri),
+            0x04 => Ok(Tnf::External),
+            0x05 => Ok(Tnf::Unknown),
+            0x06 => Ok(Tnf::Unchanged),
+            _ => Err(NdefError::InvalidRecord("reserved TNF")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub tnf: Tnf,
+    pub record_type: Vec<u8>,
+    pub id: Vec<u8>,
+    pub payload: Vec<u8>,
+}
+
+impl Record {
+    pub fn new(tnf: Tnf, record_type: &[u8], payload: &[u8]) -> Self {
+        Record {
+            tnf,
+            record_type: record_type.to_vec(),
+            id: Vec::new(),
+            payload: payload.to_vec(),
+        }
+    }
+
+    pub fn mime(mime_type: &str, payload: &[u8]) -> Self {
+        Record::new(Tnf::Mime, mime_type.as_bytes(), payload)
+    }
+
+    /// Well-known "U"; самый длинный подходящий префикс заменяется кодом.
+    pub fn uri(uri: &str) -> Self {
+        let (code, prefix) = URI_PREFIXES
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, p)| uri.starts_with(*p))
+            .max_by_key(|(_, p)| p.len())
+            .unwrap_or((0, &""));
+        let mut payload = vec![code as u8];
+        payload.extend_from_slice(&uri.as_bytes()[prefix.len()..]);
+        Record::new(Tnf::WellKnown, b"U", &payload)
+    }
+
+    /// Well-known "T" в UTF-8. Длина языка – 6 бит байта статуса.
+    pub fn text(lang: &str, text: &str) -> Result<Self, NdefError> {
+        let lang_len = check_len("language tag", lang.len(), MAX_LANG_LEN)?;
+        let mut payload = vec![lang_len as u8];
+        payload.extend_from_slice(lang.as_bytes());
+        payload.extend_from_slice(text.as_bytes());
+        Ok(Record::new(Tnf::WellKnown, b"T", &payload))
+    }
+
+    /// External type вида "domain.com:type"; тип по спецификации регистронезависимый.
+    pub fn external(domain_type: &str, payload: &[u8]) -> Self {
+        Record::new(
+            Tnf::External,
+            domain_type.to_ascii_lowercase().as_bytes(),
+            payload,
+        )
+    }
+
+    pub fn is_mime(&self, mime_type: &str) -> bool {
+        // MIME-типы сравниваются без учёта регистра (RFC 2045)
+        self.tnf == Tnf::Mime && self.record_type.eq_ignore_ascii_case(mime_type.as_bytes())
+    }
+
+    pub fn as_uri(&self) -> Option<String> {
+        if self.tnf != Tnf::WellKnown || self.record_type != b"U" || self.payload.is_empty() {
+            return None;
+        }
+        let prefix = URI_PREFIXES.get(self.payload[0] as usize).unwrap_or(&"");
+        let rest = std::str::from_utf8(&self.payload[1..]).ok()?;
+        Some(format!("{}{}", prefix, rest))
+    }
+
+    /// Возвращает (язык, текст). UTF-16 тексты не поддерживаются.
+    pub fn as_text(&self) -> Option<(String, String)> {
+        if self.tnf != Tnf::WellKnown || self.record_type != b"T" || self.payload.is_empty() {
+            return None;
+        }
+        let status = self.payload[0];
+        if status & 0x80 != 0 {
+            return None;
+        }
+        let lang_len = (status & 0x3F) as usize;
+        if self.payload.len() < 1 + lang_len {
+            return None;
+        }
+        let lang = std::str::from_utf8(&self.payload[1..1 + lang_len]).ok()?;
+        let text = std::str::from_utf8(&self.payload[1 + lang_len..]).ok()?;
+        Some((lang.to_string(), text.to_string()))
+    }
+
+    fn encode_into(
+        &self,
+        out: &mut Vec<u8>,
+        mb: bool,
+        me: bool,
+        cf: bool,
+    ) -> Result<(), NdefError> {
+        let type_len = check_len("type", self.record_type.len(), u8::MAX as usize)?;
+        let id_len = check_len("id", self.id.len(), u8::MAX as usize)?;
+        let payload_len = check_len("payload", self.payload.len(), u32::MAX as usize)?;
+        let mut header = self.tnf as u8;
+        if mb {
+            header |= FLAG_MB;
+        }
+        if me {
+            header |= FLAG_ME;
+        }
+        if cf {
+            header |= FLAG_CF;
+        }
+        let short = self.payload.len() < 256;
+        if short {
+            header |= FLAG_SR;
+        }
+        if !self.id.is_empty() {
+            header |= FLAG_IL;
+        }
+        out.push(header);
+        out.push(type_len as u8);
+        if short {
+            out.push(payload_len as u8);
+        } else {
+            out.extend_from_slice(&(payload_len as u32).to_be_bytes());
+        }
+        if !self.id.is_empty() {
+            out.push(id_len as u8);
+        }
+        out.extend_from_slice(&self.record_type);
+        out.extend_from_slice(&self.id);
+        out.extend_from_slice(&self.payload);
+        Ok(())
+    }
+}
+
+fn check_len(field: &'static str, len: usize, max: usize) -> Result<usize, NdefError> {
+    if len > max {
+        return Err(NdefError::FieldTooLong { field, len, max });
+    }
+    Ok(len)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Message {
+    pub records: Vec<Record>,
+}
+
+impl Message {
+    pub fn new(records: Vec<Record>) -> Self {
+        Message { records }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, NdefError> {
+        self.to_bytes_chunked(None)
+    }
+
+    /// Кодирует сообщение; если задан `chunk_size`, длинные payload-ы
+    /// разбиваются на chunked-записи (CF=1, последующие куски с TNF=Unchanged).
+    /// Тип, id или payload длиннее своего поля длины – `FieldTooLong`.
+    pub fn to_bytes_chunked(&self, chunk_size: Option<usize>) -> Result<Vec<u8>, NdefError> {
+        let mut out = Vec::new();
+        let count = self.records.len();
+        for (i, record) in self.records.iter().enumerate() {
+            let first = i == 0;
+            let last = i + 1 == count;
+            let chunk_size = match chunk_size {
+                Some(size) if size > 0 && record.payload.len() > size => size,
+                _ => {
+                    record.encode_into(&mut out, first, last, false)?;
+                    continue;
+                }
+            };
+
+            let chunks: Vec<&[u8]> = record.payload.chunks(chunk_size).collect();
+            for (n, chunk) in chunks.iter().enumerate() {
+                let final_chunk = n + 1 == chunks.len();
+                let part = if n == 0 {
+                    Record {
+                        tnf: record.tnf,
+                        record_type: record.record_type.clone(),
+                        id: record.id.clone(),
+                        payload: chunk.to_vec(),
+                    }
+                } else {
+                    Record::new(Tnf::Unchanged, &[], chunk)
+                };
+                part.encode_into(&mut out, first && n == 0, last && final_chunk, !final_chunk)?;
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, NdefError> {
+        // Пустой NDEF TLV (03 00 FE) – так выглядит свежеотформатированная метка
+        if bytes.is_empty() {
+            return Err(NdefError::NoMessage);
+        }
+        let mut records = Vec::new();
+        let mut pos = 0;
+        // Запись, собираемая из chunked-кусков
+        let mut chunked: Option<Record> = None;
+        let mut seen_end = false;
+
+        while pos < bytes.len() {
+            if seen_end {
+                return Err(NdefError::InvalidMessage("data after ME record"));
+            }
+            let header = bytes[pos];
+            if pos == 0 && header & FLAG_MB == 0 {
+                return Err(NdefError::InvalidMessage("first record without MB"));
+            }
+            if pos != 0 && header & FLAG_MB != 0 {
+                return Err(NdefError::InvalidMessage("MB set on non-first record"));
+            }
+            let tnf = Tnf::from_bits(header)?;
+            let short = header & FLAG_SR != 0;
+            let has_id = header & FLAG_IL != 0;
+
+            let mut cursor = pos + 1;
+            let type_len = *bytes.get(cursor).ok_or(NdefError::Truncated)? as usize;
+            cursor += 1;
+            let payload_len = if short {
+                let len = *bytes.get(cursor).ok_or(NdefError::Truncated)? as usize;
+                cursor += 1;
+                len
+            } else {
+                let raw = bytes.get(cursor..cursor + 4).ok_or(NdefError::Truncated)?;
+                cursor += 4;
+                u32::from_be_bytes(raw.try_into().unwrap()) as usize
+            };
+            let id_len = if has_id {
+                let len = *bytes.get(cursor).ok_or(NdefError::Truncated)? as usize;
+                cursor += 1;
+                len
+            } else {
+                0
+            };
+
+            let record_type = take(bytes, &mut cursor, type_len)?;
+            let id = take(bytes, &mut cursor, id_len)?;
+            let payload = take(bytes, &mut cursor, payload_len)?;
+            pos = cursor;
+
+            if (tnf == Tnf::Empty || tnf == Tnf::Unknown || tnf == Tnf::Unchanged) && type_len != 0
+            {
+                return Err(NdefError::InvalidRecord("type must be empty for this TNF"));
+            }
+
+            let cf = header & FLAG_CF != 0;
+            match chunked.as_mut() {
+                Some(acc) => {
+                    if tnf != Tnf::Unchanged || has_id {
+                        return Err(NdefError::InvalidRecord(
+                            "middle chunk must be TNF=unchanged",
+                        ));
+                    }
+                    acc.payload.extend_from_slice(payload);
+                    if !cf {
+                        records.push(chunked.take().unwrap());
+                    }
+                }
+                None => {
+                    if tnf == Tnf::Unchanged {
+                        return Err(NdefError::InvalidRecord("unexpected TNF=unchanged"));
+                    }
+                    let record = Record {
+                        tnf,
+                        record_type: record_type.to_vec(),
+                        id: id.to_vec(),
+                        payload: payload.to_vec(),
+                    };
+                    if cf {
+                        chunked = Some(record);
+                    } else {
+                        records.push(record);
+                    }
+                }
+            }
+
+            if header & FLAG_ME != 0 {
+                seen_end = true;
+            }
+        }
+
+        if chunked.is_some() {
+            return Err(NdefError::InvalidMessage("unterminated chunked record"));
+        }
+        if !seen_end {
+            return Err(NdefError::InvalidMessage("no record with ME"));
+        }
+        Ok(Message { records })
+    }
+
+    pub fn find_mime(&self, mime_type: &str) -> Option<&Record> {
+        self.records.iter().find(|r| r.is_mime(mime_type))
+    }
+}
+
+fn take<'a>(bytes: &'a [u8], cursor: &mut usize, len: usize) -> Result<&'a [u8], NdefError> {
+    let slice = bytes
+        .get(*cursor..*cursor + len)
+        .ok_or(NdefError::Truncated)?;
+    *cursor += len;
+    Ok(slice)
+}
+
+/// Оборачивает NDEF-сообщение в TLV для записи в область данных метки:
+/// `03 LEN MESSAGE FE`, где LEN >= 0xFF кодируется как `FF HI LO`.
+/// Трёхбайтовая длина ограничена 0xFFFE (0xFFFF зарезервировано).
+pub fn wrap_tlv(message: &[u8]) -> Result<Vec<u8>, NdefError> {
+    let mut out = vec![TLV_NDEF_MESSAGE];
+    if message.len() < 0xFF {
+        out.push(message.len() as u8);
+    } else {
+        let len = u16::try_from(message.len())
+            .ok()
+            .filter(|&len| len < 0xFFFF)
+            .ok_or(NdefError::TooLong(message.len()))?;
+        out.push(0xFF);
+        out.extend_from_slice(&len.to_be_bytes());
+    }
+    out.extend_from_slice(message);
+    out.push(TLV_TERMINATOR);
+    Ok(out)
+}
+
+/// Ищет первый NDEF TLV в области данных и возвращает байты сообщения.
+pub fn find_ndef_tlv(area: &[u8]) -> Result<&[u8], NdefError> {
+    let mut pos = 0;
+    while pos < area.len() {
+        let tlv_type = area[pos];
+        pos += 1;
+        match tlv_type {
+            TLV_NULL => continue,
+            TLV_TERMINATOR => break,
+            _ => {}
+        }
+        let mut len = *area.get(pos).ok_or(NdefError::Truncated)? as usize;
+        pos += 1;
+        if len == 0xFF {
+            let raw = area.get(pos..pos + 2).ok_or(NdefError::Truncated)?;
+            len = u16::from_be_bytes([raw[0], raw[1]]) as usize;
+            pos += 2;
+        }
+        let value = area.get(pos..pos + len).ok_or(NdefError::Truncated)?;
+        if tlv_type == TLV_NDEF_MESSAGE {
+            return Ok(value);
+        }
+        // Lock/Memory Control и проприетарные TLV пропускаем
+        pos += len;
+    }
+    Err(NdefError::NoMessage)
+}
+
+/// Достаёт payload первой MIME-записи нужного типа из области данных метки.
+pub fn extract_mime_payload(area: &[u8], mime_type: &str) -> Result<Vec<u8>, NdefError> {
+    let message = Message::parse(find_ndef_tlv(area)?)?;
+    message
+        .find_mime(mime_type)
+        .map(|r| r.payload.clone())
+        .ok_or_else(|| NdefError::RecordNotFound(mime_type.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_record_round_trip() {
+        let message = Message::new(vec![Record::mime(CTF_MIME_TYPE, b"\x03payload")]);
+        let bytes = message.to_bytes().unwrap();
+        // MB|ME|SR, TNF=MIME
+        assert_eq!(bytes[0], 0xD2);
+        assert_eq!(bytes[1], CTF_MIME_TYPE.len() as u8);
+        assert_eq!(bytes[2], 8);
+        assert_eq!(Message::parse(&bytes).unwrap(), message);
+    }
+
+    #[test]
+    fn long_record_and_several_records_round_trip() {
+        let payload: Vec<u8> = (0..600).map(|i| i as u8).collect();
+        let mut with_id = Record::mime(CTF_MIME_TYPE, &payload);
+        with_id.id = b"id".to_vec();
+        let message = Message::new(vec![
+            Record::uri("https://www.example.com/flag"),
+            Record::text("en", "hello").unwrap(),
+            with_id,
+        ]);
+        let bytes = message.to_bytes().unwrap();
+        let parsed = Message::parse(&bytes).unwrap();
+        assert_eq!(parsed, message);
+        assert_eq!(
+            parsed.records[0].as_uri().as_deref(),
+            Some("https://www.example.com/flag")
+        );
+        assert_eq!(parsed.records[0].payload[0], 0x02);
+        assert_eq!(
+            parsed.records[1].as_text(),
+            Some(("en".to_string(), "hello".to_string()))
+        );
+        // Длинная запись: без SR, длина 4 байтами
+        let long = bytes.len() - (payload.len() + 2 + CTF_MIME_TYPE.len() + 1 + 4 + 2);
+        assert_eq!(bytes[long] & FLAG_SR, 0);
+        assert_eq!(&bytes[long + 2..long + 6], &600u32.to_be_bytes());
+    }
+
+    #[test]
+    fn chunked_record_round_trip() {
+        let payload: Vec<u8> = (0..100).collect();
+        let message = Message::new(vec![Record::mime(CTF_MIME_TYPE, &payload)]);
+        let bytes = message.to_bytes_chunked(Some(40)).unwrap();
+        // 40 + 40 + 20: первый кусок с типом и CF, дальше TNF=Unchanged
+        assert_eq!(bytes[0], FLAG_MB | FLAG_CF | FLAG_SR | Tnf::Mime as u8);
+        assert_ne!(bytes, message.to_bytes().unwrap());
+        assert_eq!(Message::parse(&bytes).unwrap(), message);
+        // Короткий payload не режется
+        assert_eq!(
+            message.to_bytes_chunked(Some(200)).unwrap(),
+            message.to_bytes().unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_fields_longer_than_their_length_byte() {
+        let lang = "x".repeat(63);
+        let record = Record::text(&lang, "hi").unwrap();
+        assert_eq!(record.as_text(), Some((lang, "hi".to_string())));
+        assert_eq!(
+            Record::text(&"x".repeat(64), "hi"),
+            Err(NdefError::FieldTooLong {
+                field: "language tag",
+                len: 64,
+                max: 63
+            })
+        );
+
+        let mime = "a/".to_string() + &"b".repeat(253);
+        let bytes = Message::new(vec![Record::mime(&mime, b"x")])
+            .to_bytes()
+            .unwrap();
+        assert_eq!(
+            Message::parse(&bytes).unwrap().records[0].record_type.len(),
+            255
+        );
+        let long_type = Message::new(vec![Record::mime(&(mime + "b"), b"x")]);
+        assert_eq!(
+            long_type.to_bytes(),
+            Err(NdefError::FieldTooLong {
+                field: "type",
+                len: 256,
+                max: 255
+            })
+        );
+
+        let mut record = Record::mime(CTF_MIME_TYPE, b"x");
+        record.id = vec![0x49; 255];
+        let bytes = Message::new(vec![record.clone()]).to_bytes().unwrap();
+        assert_eq!(Message::parse(&bytes).unwrap().records[0], record);
+        record.id.push(0x49);
+        // Первый кусок chunked-записи несёт id – проверяется и там
+        assert_eq!(
+            Message::new(vec![record]).to_bytes_chunked(Some(1)),
+            Err(NdefError::FieldTooLong {
+                field: "id",
+                len: 256,
+                max: 255
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        let bytes = Message::new(vec![Record::mime(CTF_MIME_TYPE, b"data")])
+            .to_bytes()
+            .unwrap();
+        for len in 1..bytes.len() {
+            assert!(Message::parse(&bytes[..len]).is_err(), "prefix {}", len);
+        }
+        assert_eq!(
+            Message::parse(&bytes[..bytes.len() - 1]),
+            Err(NdefError::Truncated)
+        );
+        assert_eq!(Message::parse(&[]), Err(NdefError::NoMessage));
+
+        let mut no_mb = bytes.clone();
+        no_mb[0] &= !FLAG_MB;
+        assert_eq!(
+            Message::parse(&no_mb),
+            Err(NdefError::InvalidMessage("first record without MB"))
+        );
+        let mut no_me = bytes.clone();
+        no_me[0] &= !FLAG_ME;
+        assert_eq!(
+            Message::parse(&no_me),
+            Err(NdefError::InvalidMessage("no record with ME"))
+        );
+        let mut reserved = bytes.clone();
+        reserved[0] |= 0x07;
+        assert_eq!(
+            Message::parse(&reserved),
+            Err(NdefError::InvalidRecord("reserved TNF"))
+        );
+        let mut trailing = bytes.clone();
+        trailing.extend_from_slice(&bytes);
+        assert_eq!(
+            Message::parse(&trailing),
+            Err(NdefError::InvalidMessage("data after ME record"))
+        );
+
+        let chunked = Message::new(vec![Record::mime(CTF_MIME_TYPE, &[0; 50])])
+            .to_bytes_chunked(Some(20))
+            .unwrap();
+        // Обрезаем последний кусок целиком – CF так и не сброшен
+        let first_two = 3 + CTF_MIME_TYPE.len() + 20 + 3 + 20;
+        let mut unterminated = chunked[..first_two].to_vec();
+        unterminated[first_two - 23] |= FLAG_ME;
+        assert_eq!(
+            Message::parse(&unterminated),
+            Err(NdefError::InvalidMessage("unterminated chunked record"))
+        );
+    }
+
+    #[test]
+    fn tlv_wrapping_and_lookup() {
+        let message = Message::new(vec![Record::mime(CTF_MIME_TYPE, b"\x03abc")])
+            .to_bytes()
+            .unwrap();
+        let tlv = wrap_tlv(&message).unwrap();
+        assert_eq!(tlv[..2], [TLV_NDEF_MESSAGE, message.len() as u8]);
+        assert_eq!(*tlv.last().unwrap(), TLV_TERMINATOR);
+
+        // Lock Control и NULL перед сообщением пропускаются
+        let mut area = vec![TLV_LOCK_CONTROL, 3, 0xA0, 0x10, 0x44, TLV_NULL];
+        area.extend_from_slice(&tlv);
+        area.extend_from_slice(&[0; 16]);
+        assert_eq!(find_ndef_tlv(&area).unwrap(), &message[..]);
+        assert_eq!(
+            extract_mime_payload(&area, "APPLICATION/X-CTF").unwrap(),
+            b"\x03abc"
+        );
+        assert_eq!(
+            extract_mime_payload(&area, "text/plain"),
+            Err(NdefError::RecordNotFound("text/plain".to_string()))
+        );
+
+        let long = vec![0u8; 300];
+        let tlv = wrap_tlv(&long).unwrap();
+        assert_eq!(tlv[..4], [TLV_NDEF_MESSAGE, 0xFF, 0x01, 0x2C]);
+        assert_eq!(find_ndef_tlv(&tlv).unwrap().len(), 300);
+        assert_eq!(find_ndef_tlv(&tlv[..100]), Err(NdefError::Truncated));
+        assert_eq!(
+            find_ndef_tlv(&[TLV_TERMINATOR, TLV_NDEF_MESSAGE]),
+            Err(NdefError::NoMessage)
+        );
+        assert_eq!(
+            find_ndef_tlv(&[TLV_NDEF_MESSAGE]),
+            Err(NdefError::Truncated)
+        );
+
+        assert!(wrap_tlv(&vec![0; 0xFFFE]).is_ok());
+        assert_eq!(wrap_tlv(&vec![0; 0xFFFF]), Err(NdefError::TooLong(0xFFFF)));
+        assert_eq!(wrap_tlv(&vec![0; 70_000]), Err(NdefError::TooLong(70_000)));
+    }
+}
@@ -0,0 +1,59 @@
+/// Чем закончилась обработка карты – общий словарь nfc_reader и griphd.
+///
+/// `ReadOk`/`ReadFail` определяет сам nfc_reader, остальное присылает griphd
+/// датаграммой с именем исхода на `OUTCOME_SOCKET`. Сокет лежит в
+/// `RuntimeDirectory` сервиса nfc_reader, а не в общем /tmp.
+pub const OUTCOME_SOCKET: &str = "/run/nfc_reader/outcome.sock";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// Payload прочитан с карты и передан griphd.
+    ReadOk,
+    /// Карта не прочиталась или payload на ней не нашёлся.
+    ReadFail,
+    /// griphd отверг payload: не тот тип, короткий или не сошлась CRC.
+    PayloadRejected,
+    /// Программа выполнена, но до флага не дошла.
+    Executed,
+    FlagSent,
+}
+
+impl Outcome {
+    pub const ALL: [Outcome; 5] = [
+        Outcome::ReadOk,
+        Outcome::ReadFail,
+        Outcome::PayloadRejected,
+        Outcome::Executed,
+        Outcome::FlagSent,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::ReadOk => "read-ok",
+            Outcome::ReadFail => "read-fail",
+            Outcome::PayloadRejected => "payload-rejected",
+            Outcome::Executed => "executed",
+            Outcome::FlagSent => "flag-sent",
+        }
+    }
+
+    /// Имя из `as_str`; пробелы и перевод строки по краям не мешают.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        Outcome::ALL.into_iter().find(|o| o.as_str() == text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for outcome in Outcome::ALL {
+            assert_eq!(Outcome::parse(outcome.as_str()), Some(outcome));
+        }
+        assert_eq!(Outcome::parse("flag-sent\n"), Some(Outcome::FlagSent));
+        assert_eq!(Outcome::parse("flag"), None);
+    }
+}
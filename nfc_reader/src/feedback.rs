@@ -0,0 +1,285 @@
+use gpiocdev::line::Value;
+use gpiocdev::Request;
+pub use nfc_format::outcome::Outcome;
+use std::collections::HashMap;
+use std::fs::{DirBuilder, Permissions};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+/// Светодиод или зуммер: включён/выключен.
+pub trait Output: Send {
+    fn set(&mut self, on: bool) -> io::Result<()>;
+}
+
+/// Выход на линии GPIO через gpiocdev, активный уровень – 1.
+pub struct GpioOutput {
+    request: Request,
+    offset: u32,
+}
+
+impl GpioOutput {
+    /// `chip` – `/dev/gpiochip0` или `gpiochip0`. Линия сразу гасится.
+    pub fn new(chip: &str, line: u32) -> io::Result<Self> {
+        let path = if chip.starts_with('/') {
+            chip.to_string()
+        } else {
+            format!("/dev/{}", chip)
+        };
+        let request = Request::builder()
+            .on_chip(path)
+            .with_consumer("nfc_reader")
+            .with_line(line)
+            .as_output(Value::Inactive)
+            .request()
+            .map_err(io::Error::other)?;
+        Ok(GpioOutput {
+            request,
+            offset: line,
+        })
+    }
+}
+
+impl Output for GpioOutput {
+    fn set(&mut self, on: bool) -> io::Result<()> {
+        let value = if on { Value::Active } else { Value::Inactive };
+        self.request
+            .set_value(self.offset, value)
+            .map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+/// Шаг шаблона: какие выходы горят и сколько.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub on: Vec<String>,
+    pub duration: Duration,
+}
+
+/// Шаблон `green+buzzer:200,off:100,green:200`: шаги через запятую, в шаге
+/// включённые выходы через `+` (или `off`) и длительность в миллисекундах.
+/// Выходы, не упомянутые в шаге, выключены.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern(pub Vec<Step>);
+
+impl Pattern {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let steps = text
+            .split(',')
+            .map(|step| {
+                let (outputs, ms) = step
+                    .trim()
+                    .split_once(':')
+                    .ok_or_else(|| format!("step {:?} is not OUTPUTS:MS", step))?;
+                let ms: u64 = ms
+                    .parse()
+                    .map_err(|e| format!("bad duration in {:?}: {}", step, e))?;
+                let on = match outputs {
+                    "off" => Vec::new(),
+                    _ => outputs.split('+').map(str::to_string).collect(),
+                };
+                Ok(Step {
+                    on,
+                    duration: Duration::from_millis(ms),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Pattern(steps))
+    }
+}
+
+/// Шаблоны по умолчанию для выходов `green`, `red` и `buzzer`.
+pub fn default_pattern(outcome: Outcome) -> Pattern {
+    let text = match outcome {
+        Outcome::ReadOk => "green:300",
+        Outcome::ReadFail => "red:150,off:100,red:150",
+        Outcome::PayloadRejected => "red+buzzer:600",
+        Outcome::Executed => "green:150,off:100,green:150",
+        Outcome::FlagSent => "green+buzzer:100,off:100,green+buzzer:100,off:100,green+buzzer:500",
+    };
+    Pattern::parse(text).unwrap()
+}
+
+/// Выходы по именам и шаблон для каждого исхода. Шаблон может ссылаться на
+/// неподключённый выход – такой шаг просто ничего не зажигает.
+pub struct Feedback {
+    outputs: HashMap<String, Box<dyn Output>>,
+    patterns: HashMap<Outcome, Pattern>,
+}
+
+impl Default for Feedback {
+    fn default() -> Self {
+        Feedback::new()
+    }
+}
+
+impl Feedback {
+    pub fn new() -> Self {
+        Feedback {
+            outputs: HashMap::new(),
+            patterns: Outcome::ALL
+                .into_iter()
+                .map(|outcome| (outcome, default_pattern(outcome)))
+                .collect(),
+        }
+    }
+
+    pub fn with_output(mut self, name: &str, output: Box<dyn Output>) -> Self {
+        self.outputs.insert(name.to_string(), output);
+        self
+    }
+
+    pub fn with_pattern(mut self, outcome: Outcome, pattern: Pattern) -> Self {
+        self.patterns.insert(outcome, pattern);
+        self
+    }
+
+    /// Проигрывает шаблон исхода; в конце всё выключено.
+    pub fn play(&mut self, outcome: Outcome) -> io::Result<()> {
+        let Some(Pattern(steps)) = self.patterns.get(&outcome) else {
+            return Ok(());
+        };
+        for step in steps {
+            for (name, output) in self.outputs.iter_mut() {
+                output.set(step.on.contains(name))?;
+            }
+            thread::sleep(step.duration);
+        }
+        for output in self.outputs.values_mut() {
+            output.set(false)?;
+        }
+        Ok(())
+    }
+
+    /// Проигрывание в отдельном потоке, чтобы не задерживать опрос карт.
+    /// Исходы, пришедшие во время шаблона, проигрываются по очереди.
+    pub fn spawn(mut self) -> Sender<Outcome> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for outcome in receiver {
+                if let Err(e) = self.play(outcome) {
+                    warn!(error = %e, "feedback output failed");
+                }
+            }
+        });
+        sender
+    }
+}
+
+/// Принимает исходы от griphd на `path` (Unix datagram) и пересылает их в
+/// `sender`. Старый сокет от прошлого запуска удаляется. Каталог сокета
+/// (под systemd – `RuntimeDirectory`) и сам сокет доступны только владельцу:
+/// иначе исход мог бы прислать кто угодно.
+pub fn listen_outcomes(path: &Path, sender: Sender<Outcome>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    let _ = std::fs::remove_file(path);
+    let socket = UnixDatagram::bind(path)?;
+    std::fs::set_permissions(path, Permissions::from_mode(0o600))?;
+    thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        while let Ok(len) = socket.recv(&mut buf) {
+            match std::str::from_utf8(&buf[..len])
+                .ok()
+                .and_then(Outcome::parse)
+            {
+                Some(outcome) => {
+                    if sender.send(outcome).is_err() {
+                        break;
+                    }
+                }
+                None => {
+                    warn!(datagram = %hex::encode_upper(&buf[..len]), "unknown outcome from griphd")
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Выход для тестов: запоминает все переключения. Клоны делят один журнал.
+    #[derive(Debug, Clone, Default)]
+    struct MockOutput {
+        states: Arc<Mutex<Vec<bool>>>,
+    }
+
+    impl MockOutput {
+        fn states(&self) -> Vec<bool> {
+            self.states.lock().unwrap().clone()
+        }
+    }
+
+    impl Output for MockOutput {
+        fn set(&mut self, on: bool) -> io::Result<()> {
+            self.states.lock().unwrap().push(on);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn parses_patterns() {
+        assert_eq!(
+            Pattern::parse("green+buzzer:200, off:50").unwrap(),
+            Pattern(vec![
+                Step {
+                    on: vec!["green".into(), "buzzer".into()],
+                    duration: Duration::from_millis(200),
+                },
+                Step {
+                    on: Vec::new(),
+                    duration: Duration::from_millis(50),
+                },
+            ])
+        );
+        assert!(Pattern::parse("green").is_err());
+        assert!(Pattern::parse("green:soon").is_err());
+        for outcome in Outcome::ALL {
+            assert!(!default_pattern(outcome).0.is_empty());
+        }
+    }
+
+    #[test]
+    fn plays_pattern_on_named_outputs() {
+        let green = MockOutput::default();
+        let red = MockOutput::default();
+        let mut feedback = Feedback::new()
+            .with_output("green", Box::new(green.clone()))
+            .with_output("red", Box::new(red.clone()))
+            .with_pattern(
+                Outcome::FlagSent,
+                Pattern::parse("green+buzzer:1,off:1,green:1").unwrap(),
+            );
+        feedback.play(Outcome::FlagSent).unwrap();
+        assert_eq!(green.states(), [true, false, true, false]);
+        assert_eq!(red.states(), [false, false, false, false]);
+    }
+
+    #[test]
+    fn forwards_outcomes_from_griphd() {
+        let path =
+            std::env::temp_dir().join(format!("nfc_reader_outcome_{}.sock", std::process::id()));
+        let (sender, receiver) = mpsc::channel();
+        listen_outcomes(&path, sender).unwrap();
+
+        let griphd = UnixDatagram::unbound().unwrap();
+        griphd.send_to(b"bogus", &path).unwrap();
+        griphd.send_to(b"payload-rejected", &path).unwrap();
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(1)),
+            Ok(Outcome::PayloadRejected)
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1 +1,6 @@
+pub mod feedback;
+pub mod logging;
+pub mod metrics;
+pub mod notify;
 pub mod pn532reader;
+pub mod status;
@@ -0,0 +1,63 @@
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing_subscriber::EnvFilter;
+
+/// Скрывать ли содержимое карт, фреймов и payload в логах.
+static REDACT: AtomicBool = AtomicBool::new(false);
+
+/// Фильтр по умолчанию, если не задан ни `--log`, ни `RUST_LOG`.
+pub const DEFAULT_FILTER: &str = "info";
+
+/// Настраивает tracing: `filter` – директивы EnvFilter
+/// (`info,nfc_reader::pn532reader::transport=trace`), без него берётся
+/// `RUST_LOG`. `json` – по объекту на строку для сборщика логов.
+pub fn init(filter: Option<&str>, json: bool, redact: bool) -> Result<(), String> {
+    REDACT.store(redact, Ordering::Relaxed);
+    let filter = match filter {
+        Some(directives) => EnvFilter::try_new(directives).map_err(|e| e.to_string())?,
+        None => {
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER))
+        }
+    };
+    // В journald цвета только мешают
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(std::io::stdout().is_terminal());
+    let result = if json {
+        builder.json().try_init()
+    } else {
+        builder.try_init()
+    };
+    result.map_err(|e| e.to_string())
+}
+
+/// Байты для лога: hex или, при `--redact-payload`, только длина.
+pub fn bytes(data: &[u8]) -> Bytes<'_> {
+    Bytes(data)
+}
+
+pub struct Bytes<'a>(&'a [u8]);
+
+impl fmt::Display for Bytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if REDACT.load(Ordering::Relaxed) {
+            write!(f, "<{} bytes redacted>", self.0.len())
+        } else {
+            write!(f, "{}", hex::encode_upper(self.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_bytes_on_request() {
+        assert_eq!(bytes(&[0xDE, 0xAD]).to_string(), "DEAD");
+        REDACT.store(true, Ordering::Relaxed);
+        assert_eq!(bytes(&[0xDE, 0xAD]).to_string(), "<2 bytes redacted>");
+        REDACT.store(false, Ordering::Relaxed);
+    }
+}
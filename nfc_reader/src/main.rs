@@ -1,88 +1,671 @@
 extern crate nfc_reader;
-use clap::Parser;
-use hex;
-use nfc_reader::pn532reader::device::PN532;
-use std::fs::File;
-use std::io::Write;
+use clap::{Parser, Subcommand, ValueEnum};
+use nfc_format::layout;
+use nfc_format::ndef::{self, CTF_MIME_TYPE};
+use nfc_format::outcome::OUTCOME_SOCKET;
+use nfc_reader::feedback::{listen_outcomes, Feedback, GpioOutput, Outcome, Pattern};
+use nfc_reader::logging;
+use nfc_reader::metrics::{self, DEFAULT_METRICS_ADDR, METRICS};
+use nfc_reader::notify::{self, Heartbeat};
+use nfc_reader::pn532reader::card::{classic_sector_of, CardType, MemoryMap};
+use nfc_reader::pn532reader::device::SectorStatus;
+use nfc_reader::pn532reader::device::{Target, PN532};
+use nfc_reader::pn532reader::emulation::Type4Emulator;
+use nfc_reader::pn532reader::error::Pn532Error;
+use nfc_reader::pn532reader::keys::{KeyStore, KeyType};
+use nfc_reader::pn532reader::ntag::NtagModel;
+use nfc_reader::pn532reader::power::{GpioResetPin, Watchdog, WAKE_ON_HOST};
+use nfc_reader::pn532reader::presence::{PresenceEvent, PresenceTracker};
+use nfc_reader::pn532reader::rfconfig::{AnalogTypeA, RfConfig, RfTimeout};
+use nfc_reader::pn532reader::transport::{
+    HsuTransport, I2cTransport, IrqLine, IrqTransport, SpiTransport, Transport,
+};
+use nfc_reader::status::{self, DEFAULT_MAX_POLL_AGE};
+use std::path::Path;
+use std::sync::mpsc::Sender;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, trace, warn};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum TransportKind {
+    I2c,
+    Spi,
+    Hsu,
+}
 
 #[derive(Parser, Debug)]
 struct Args {
-    /// I2C device path
+    /// Bus the PN532 is connected to
+    #[arg(short, long, value_enum, default_value = "i2c")]
+    transport: TransportKind,
+    /// I2C, SPI or serial device path
     #[arg(short, long, default_value = "/dev/i2c-0")]
     device: String,
     /// I2C address of PN532 (default 0x24)
     #[arg(short, long, default_value = "0x24")]
     address: String,
-    /// Key auth of card (default 0x24)
+    /// SPI clock in Hz
+    #[arg(long, default_value_t = 1_000_000)]
+    spi_speed: u32,
+    /// Let the SPI controller send LSB first instead of reversing bits in software
+    #[arg(long)]
+    spi_hw_lsb: bool,
+    /// UART speed for HSU; the PN532 powers up at 115200
+    #[arg(long, default_value_t = 115_200)]
+    baud: u32,
+    /// GPIO chip with the PN532 IRQ pin (e.g. gpiochip0); wait for readiness
+    /// on IRQ edges instead of polling the bus
+    #[arg(long, requires = "irq_line")]
+    irq_chip: Option<String>,
+    /// Line offset of the PN532 IRQ pin on --irq-chip
+    #[arg(long, requires = "irq_chip")]
+    irq_line: Option<u32>,
+    /// GPIO chip with the PN532 RSTPDN pin, used to hard-reset a wedged chip
+    #[arg(long, requires = "reset_line")]
+    reset_chip: Option<String>,
+    /// Line offset of the PN532 RSTPDN pin on --reset-chip
+    #[arg(long, requires = "reset_chip")]
+    reset_line: Option<u32>,
+    /// Consecutive bus errors before the PN532 is reset and re-initialised
+    #[arg(long, default_value_t = 5)]
+    watchdog: u32,
+    /// Put the PN532 into PowerDown after this many seconds without a card,
+    /// waking it up briefly to poll
+    #[arg(long)]
+    power_down_after: Option<u64>,
+    /// Let the PN532 poll the field itself (InAutoPoll) and answer only when
+    /// a card shows up, instead of listing targets every 50 ms
+    #[arg(long)]
+    auto_poll: bool,
+    /// Missed polls in a row before a card counts as removed
+    #[arg(long, default_value_t = 3)]
+    debounce: u32,
+    /// Do not process a card again if it returns within this many seconds
+    /// after being removed
+    #[arg(long, default_value_t = 2)]
+    cooldown: u64,
+    /// LED or buzzer on a GPIO line as NAME=CHIP:LINE (e.g. green=gpiochip0:17);
+    /// the default patterns use `green`, `red` and `buzzer`
+    #[arg(long)]
+    feedback_output: Vec<String>,
+    /// Override the pattern for an outcome (read-ok, read-fail, payload-rejected,
+    /// executed, flag-sent) as OUTCOME=STEPS, e.g. read-ok=green+buzzer:200,off:100
+    #[arg(long)]
+    feedback_pattern: Vec<String>,
+    /// Unix datagram socket on which griphd reports payload outcomes
+    #[arg(long, default_value = OUTCOME_SOCKET)]
+    outcome_socket: String,
+    /// Log filter in RUST_LOG syntax, e.g. `info` or
+    /// `info,nfc_reader::pn532reader::transport=trace` (default: RUST_LOG or info)
+    #[arg(long)]
+    log: Option<String>,
+    /// Write logs as JSON, one object per line
+    #[arg(long)]
+    log_json: bool,
+    /// Log only the length of card data, frames and payloads, not their contents
+    #[arg(long)]
+    redact_payload: bool,
+    /// Address of the Prometheus `/metrics` endpoint
+    #[arg(long, default_value = DEFAULT_METRICS_ADDR)]
+    metrics_addr: String,
+    /// Do not serve metrics
+    #[arg(long)]
+    no_metrics: bool,
+    /// How many times InListPassiveTarget retries activation before reporting
+    /// an empty field (MxRtyPassiveActivation, 255 = until a card shows up)
+    #[arg(long)]
+    passive_retries: Option<u8>,
+    /// Retries of a card exchange after a timeout (MaxRtyCOM)
+    #[arg(long)]
+    com_retries: Option<u8>,
+    /// Retries of ATR_REQ when activating NFC-DEP targets (MxRtyATR)
+    #[arg(long)]
+    atr_retries: Option<u8>,
+    /// How long the PN532 waits for a card answer, in milliseconds (0.1 to 3280)
+    #[arg(long)]
+    rf_timeout_ms: Option<f32>,
+    /// How long the PN532 waits for ATR_RES, in milliseconds
+    #[arg(long)]
+    atr_timeout_ms: Option<f32>,
+    /// Receiver gain 0-7 (18-48 dB) for ISO14443A; raise it for stations behind
+    /// acrylic panels
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=7))]
+    rx_gain: Option<u8>,
+    /// All 11 analog CIU registers for ISO14443A as hex (RFConfiguration 0x0A,
+    /// default 59F43F114D85616F266287); --rx-gain is applied on top
+    #[arg(long)]
+    analog_a: Option<String>,
+    /// Key type to try first: 0x60 (key A) or 0x61 (key B)
     #[arg(short, long, default_value = "0x60")]
     key: String,
+    /// File with known MIFARE Classic keys (`KEY` or `SECTOR A|B KEY` per line);
+    /// the well-known default keys are always tried as well
+    #[arg(long)]
+    keys: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Wait for cards and store their payload for griphd (default)
+    Read,
+    /// Write a payload_gen output to the next card and read it back
+    Write {
+        /// File with the tag data area (e.g. `payload_gen -f ndef` output)
+        file: String,
+        /// Allow writing NTAG lock bytes and config pages
+        #[arg(long)]
+        force: bool,
+        /// Give up if no card has been presented within this many seconds
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+    /// Act as an NFC Forum Type 4 tag and hand an NDEF message to a phone
+    Emulate {
+        /// File with an NDEF TLV (e.g. `payload_gen -f ndef` output)
+        file: String,
+        /// Give up if no phone has read the message within this many seconds
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+    /// Ask the running reader on --metrics-addr whether it is healthy. Exits 0
+    /// when healthy, 1 when it is not and 2 when the reader does not answer
+    Status {
+        /// Unhealthy if the last successful poll is older than this many seconds
+        #[arg(long, default_value_t = DEFAULT_MAX_POLL_AGE)]
+        max_poll_age: u64,
+    },
+}
+
+/// NFCID1 в режиме карты: PN532 подставляет первым байтом 08 (случайный UID).
+const EMULATION_UID: [u8; 3] = [0x47, 0x52, 0x50];
+
+/// Сколько PN532 спит в PowerDown между опросами, когда карт давно не было.
+const IDLE_SLEEP: Duration = Duration::from_millis(500);
+
+/// Сколько ждать ответа InAutoPoll, прежде чем отменить опрос и заняться
+/// простоем и сторожем шины.
+const AUTO_POLL_WAIT_MS: u32 = 1000;
+
+/// Пауза после ошибки шины, чтобы не засыпать лог и дать PN532 прийти в себя.
+const BUS_ERROR_BACKOFF: Duration = Duration::from_millis(500);
+
+/// NDEF и GRPH на MIFARE Classic лежат начиная с сектора 1: сектор 0 занят
+/// данными производителя и MAD, трейлеры секторов пропускаем.
+/// Область обрывается на первом непрочитанном секторе – нули вместо
+/// данных не должны попасть в payload.
+fn classic_data_area(map: &MemoryMap, dump: &[u8], unreadable: &[u8]) -> Vec<u8> {
+    map.classic_data_blocks()
+        .into_iter()
+        .take_while(|block| !unreadable.contains(&classic_sector_of(*block)))
+        .filter_map(|block| dump.get(block as usize * 16..block as usize * 16 + 16))
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// Payload из области данных карты: заголовок GRPH или NDEF-запись CTF_MIME_TYPE.
+fn extract_payload(area: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if layout::is_layout(area) {
+        Ok(layout::decode(area)?)
+    } else {
+        Ok(ndef::extract_mime_payload(area, CTF_MIME_TYPE)?)
+    }
+}
+
+fn process_classic(
+    pn532: &mut PN532,
+    card: &CardType,
+    uid: &[u8],
+    keys: &KeyStore,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let MemoryMap::Classic(sectors) = card.memory_map() else {
+        return Err(format!("{:?} is not a MIFARE Classic card", card).into());
+    };
+    let dump = pn532.dump_classic(&sectors, uid, keys)?;
+    for (sector, status) in &dump.sectors {
+        match status {
+            SectorStatus::Read { key_type, key } => {
+                debug!(sector = sector.index, ?key_type, key = %hex::encode(key), "sector read")
+            }
+            SectorStatus::ReadError { key_type, .. } => warn!(
+                sector = sector.index,
+                ?key_type,
+                "key accepted, some blocks unreadable"
+            ),
+            SectorStatus::AuthFailed => warn!(sector = sector.index, "no key for sector"),
+        }
+    }
+    let unreadable = dump.unreadable_sectors();
+    if !unreadable.is_empty() {
+        warn!(sectors = ?unreadable, "sectors are unreadable and zero-filled");
+    }
+    let data = dump.data;
+    info!(len = data.len(), "card data read");
+    trace!(data = %logging::bytes(&data), "card data");
+    let area = classic_data_area(&card.memory_map(), &data, &unreadable);
+    let payload = extract_payload(&area).map_err(|e| format!("No payload on card: {}", e))?;
+    pn532.write_to_file("/tmp/rfid_input.bin", &payload)?;
+    Ok(())
+}
+
+/// Модель NTAG известна только после GET_VERSION; у Ultralight размер берём из CC.
+fn ntag_model(card: &CardType) -> Option<NtagModel> {
+    match card {
+        CardType::Ntag(model) => Some(*model),
+        _ => None,
+    }
+}
+
+fn process_ntag(pn532: &mut PN532, card: &CardType) -> Result<(), Box<dyn std::error::Error>> {
+    let model = ntag_model(card);
+    let data = pn532.ntag_read_user_data(model)?;
+    info!(len = data.len(), "NTAG user data read");
+    let payload = extract_payload(&data)?;
+    pn532.write_to_file("/tmp/rfid_input.bin", &payload)?;
+    Ok(())
+}
+
+/// Карты ISO14443-4 и телефоны в режиме HCE – NDEF-приложение Type 4.
+fn process_type4(pn532: &mut PN532, target: &Target) -> Result<(), Box<dyn std::error::Error>> {
+    let message = pn532.read_type4_ndef(target)?;
+    info!(len = message.len(), "Type 4 NDEF message read");
+    let payload = ndef::Message::parse(&message)?
+        .find_mime(CTF_MIME_TYPE)
+        .map(|record| record.payload.clone())
+        .ok_or_else(|| format!("No {} record on card", CTF_MIME_TYPE))?;
+    pn532.write_to_file("/tmp/rfid_input.bin", &payload)?;
+    Ok(())
+}
+
+fn write_card(
+    pn532: &mut PN532,
+    keys: &KeyStore,
+    file: &str,
+    force: bool,
+    timeout: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data = std::fs::read(file)?;
+    info!(
+        len = data.len(),
+        file, "place a card near the reader to write it"
+    );
+
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    let target: Target = loop {
+        if let Ok(target) = pn532.read_passive_target() {
+            break target;
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("No card presented within {} s", timeout).into());
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    let card = pn532.identify_card(&target)?;
+    info!(uid = %hex::encode_upper(&target.uid), ?card, "card detected");
+
+    match card.memory_map() {
+        MemoryMap::Pages { .. } => pn532.ntag_write_user_data(&data, ntag_model(&card), force)?,
+        MemoryMap::Classic(_) => pn532.write_classic_data(&card, &target.uid, keys, &data)?,
+        MemoryMap::Application => return Err(format!("Unsupported card {:?}", card).into()),
+    }
+    info!("written and verified");
+    pn532.in_release(target.tg)?;
+    Ok(())
+}
+
+/// Ждёт телефон и отдаёт ему NDEF-сообщение из `file`, пока кто-нибудь
+/// не прочитает его целиком или не выйдет время.
+fn emulate_tag(
+    pn532: &mut PN532,
+    file: &str,
+    timeout: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data = std::fs::read(file)?;
+    let message = ndef::find_ndef_tlv(&data)?;
+    let mut emulator = Type4Emulator::new(message);
+    info!(
+        len = message.len(),
+        "emulating a Type 4 tag, waiting for a phone"
+    );
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    while Instant::now() < deadline {
+        match pn532.emulate_type4(&mut emulator, EMULATION_UID) {
+            Ok(true) => {
+                info!("message delivered");
+                return Ok(());
+            }
+            Ok(false) => warn!("phone left before reading the whole message"),
+            Err(Pn532Error::Timeout) => {}
+            Err(e) if e.is_bus_error() => return Err(e.into()),
+            // Телефон убрали посреди обмена – ждём следующего
+            Err(e) => warn!(error = %e, "emulation session failed"),
+        }
+    }
+    Err(format!("No phone read the message within {} s", timeout).into())
+}
+
+fn open_transport(args: &Args) -> Result<Box<dyn Transport>, Box<dyn std::error::Error>> {
+    let transport: Box<dyn Transport> = match args.transport {
+        TransportKind::I2c => {
+            let address = u8::from_str_radix(args.address.trim_start_matches("0x"), 16)?;
+            Box::new(I2cTransport::new(&args.device, address)?)
+        }
+        TransportKind::Spi => Box::new(SpiTransport::new(
+            &args.device,
+            args.spi_speed,
+            args.spi_hw_lsb,
+        )?),
+        TransportKind::Hsu => Box::new(HsuTransport::new(&args.device, args.baud)?),
+    };
+    let (Some(chip), Some(line)) = (&args.irq_chip, args.irq_line) else {
+        return Ok(transport);
+    };
+    // Без IRQ читатель продолжит работать опросом – это медленнее, но не фатально
+    match IrqLine::new(chip, line) {
+        Ok(irq) => {
+            info!(chip, line, "waiting for PN532 IRQ");
+            Ok(Box::new(IrqTransport::new(transport, irq)))
+        }
+        Err(e) => {
+            warn!(chip, line, error = %e, "cannot use IRQ line, polling instead");
+            Ok(transport)
+        }
+    }
+}
+
+/// Настройки RFConfiguration из командной строки; без опций чип работает
+/// со своими значениями по умолчанию.
+fn rf_config(args: &Args) -> Result<Option<RfConfig>, Box<dyn std::error::Error>> {
+    let timeout = |ms: f32| RfTimeout::from_micros((ms * 1000.0) as u32);
+    let mut analog = match &args.analog_a {
+        Some(text) => {
+            let bytes: [u8; 11] = hex::decode(text)?
+                .try_into()
+                .map_err(|_| "--analog-a takes exactly 11 bytes")?;
+            Some(AnalogTypeA::from_bytes(bytes))
+        }
+        None => None,
+    };
+    if let Some(gain) = args.rx_gain {
+        analog = Some(analog.unwrap_or_default().with_rx_gain(gain));
+    }
+    let config = RfConfig {
+        passive_activation_retries: args.passive_retries,
+        atr_retries: args.atr_retries,
+        com_retries: args.com_retries,
+        atr_res_timeout: args.atr_timeout_ms.map(timeout),
+        retry_timeout: args.rf_timeout_ms.map(timeout),
+        analog_type_a: analog,
+    };
+    Ok((config != RfConfig::default()).then_some(config))
+}
+
+/// Новая карта: определяет тип и достаёт payload. Карта не отпускается –
+/// её присутствие дальше проверяется без повторного чтения.
+fn process_card(pn532: &mut PN532, target: &Target, keys: &KeyStore) -> Outcome {
+    let timer = METRICS.card_read_seconds.start_timer();
+    let outcome = read_card(pn532, target, keys);
+    timer.observe_duration();
+    METRICS
+        .card_reads
+        .with_label_values(&[outcome.as_str()])
+        .inc();
+    outcome
+}
+
+fn read_card(pn532: &mut PN532, target: &Target, keys: &KeyStore) -> Outcome {
+    info!(
+        uid = %hex::encode_upper(&target.uid),
+        sens_res = format_args!("{:04X}", target.sens_res),
+        sel_res = format_args!("{:02X}", target.sel_res),
+        ats = target.ats.as_deref().map(hex::encode_upper),
+        "card detected"
+    );
+    match pn532.identify_card(target) {
+        Ok(card) => {
+            info!(?card, "card identified");
+            METRICS
+                .cards_detected
+                .with_label_values(&[&format!("{:?}", card)])
+                .inc();
+            let result = match card.memory_map() {
+                MemoryMap::Pages { .. } => process_ntag(pn532, &card),
+                MemoryMap::Classic(_) => process_classic(pn532, &card, &target.uid, keys),
+                MemoryMap::Application if target.is_iso14443_4() => process_type4(pn532, target),
+                MemoryMap::Application => Err(format!("Unsupported card {:?}", card).into()),
+            };
+            match result {
+                Ok(()) => Outcome::ReadOk,
+                Err(e) => {
+                    warn!(error = %e, "cannot read card");
+                    Outcome::ReadFail
+                }
+            }
+        }
+        Err(e) => {
+            warn!(error = %e, "cannot identify card");
+            METRICS.cards_detected.with_label_values(&["unknown"]).inc();
+            Outcome::ReadFail
+        }
+    }
+}
+
+/// Светодиоды и зуммер из `--feedback-output`; без выходов обратной связи нет.
+/// Исходы от griphd принимаются на `--outcome-socket`.
+fn start_feedback(args: &Args) -> Result<Option<Sender<Outcome>>, Box<dyn std::error::Error>> {
+    if args.feedback_output.is_empty() {
+        return Ok(None);
+    }
+    let mut feedback = Feedback::new();
+    for spec in &args.feedback_output {
+        let (name, line) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("--feedback-output {:?} is not NAME=CHIP:LINE", spec))?;
+        let (chip, line) = line
+            .rsplit_once(':')
+            .ok_or_else(|| format!("--feedback-output {:?} is not NAME=CHIP:LINE", spec))?;
+        let output = GpioOutput::new(chip, line.parse()?)?;
+        feedback = feedback.with_output(name, Box::new(output));
+    }
+    for spec in &args.feedback_pattern {
+        let (outcome, pattern) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("--feedback-pattern {:?} is not OUTCOME=PATTERN", spec))?;
+        let outcome =
+            Outcome::parse(outcome).ok_or_else(|| format!("unknown outcome {:?}", outcome))?;
+        feedback = feedback.with_pattern(outcome, Pattern::parse(pattern)?);
+    }
+    let sender = feedback.spawn();
+    if let Err(e) = listen_outcomes(Path::new(&args.outcome_socket), sender.clone()) {
+        warn!(socket = %args.outcome_socket, error = %e, "cannot listen for griphd outcomes");
+    }
+    Ok(Some(sender))
+}
+
+/// `status`: печатает ответ `/status` работающего считывателя, код выхода –
+/// для `ExecStartPre` и внешних проверок.
+fn show_status(addr: &str, max_poll_age: u64) -> Result<(), Box<dyn std::error::Error>> {
+    match status::query(addr, max_poll_age) {
+        Ok((code, body)) => {
+            println!("{}", body);
+            std::process::exit(if code == 200 { 0 } else { 1 });
+        }
+        Err(e) => {
+            eprintln!("nfc_reader is not answering on {}: {}", addr, e);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Простой: PN532 засыпает на `IDLE_SLEEP` и просыпается для одного опроса.
+fn doze(pn532: &mut PN532) -> Result<(), Pn532Error> {
+    pn532.power_down(WAKE_ON_HOST)?;
+    thread::sleep(IDLE_SLEEP);
+    pn532.power_up()
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("PN532 NFC Reader\n");
     let args = Args::parse();
-    let auth_key = u8::from_str_radix(&args.key.trim_start_matches("0x"), 16)?;
-    let mut pn532 = PN532::new(&args.device)?;
-    println!("Getting firmware version...");
-    match pn532.get_firmware_version() {
-        Ok(version) => {
-            println!("Firmware version: 0x{:06X}", version);
-            let ic = (version >> 16) & 0xFF;
-            let ver = (version >> 8) & 0xFF;
-            let rev = version & 0xFF;
-            println!("IC: PN5{:02X}, Version: {}.{}", ic, ver, rev);
+    logging::init(args.log.as_deref(), args.log_json, args.redact_payload)?;
+    if let Some(Command::Status { max_poll_age }) = &args.command {
+        return show_status(&args.metrics_addr, *max_poll_age);
+    }
+    info!("PN532 NFC Reader");
+    if !args.no_metrics {
+        match metrics::serve(&args.metrics_addr) {
+            Ok(addr) => info!(%addr, "serving metrics"),
+            Err(e) => warn!(addr = %args.metrics_addr, error = %e, "cannot serve metrics"),
+        }
+    }
+    let auth_key = u8::from_str_radix(args.key.trim_start_matches("0x"), 16)?;
+    let key_type = KeyType::from_auth_command(auth_key).ok_or("Key type must be 0x60 or 0x61")?;
+    let keys = match &args.keys {
+        Some(path) => KeyStore::from_file(path)?,
+        None => KeyStore::default(),
+    }
+    .with_preferred(key_type);
+    let mut pn532 = PN532::with_transport(open_transport(&args)?);
+    if let Some(config) = rf_config(&args)? {
+        info!(?config, "RF configuration");
+        pn532 = pn532.with_rf_config(config);
+    }
+    if let (Some(chip), Some(line)) = (&args.reset_chip, args.reset_line) {
+        match GpioResetPin::new(chip, line) {
+            Ok(pin) => pn532 = pn532.with_reset_pin(Box::new(pin)),
+            Err(e) => warn!(chip, line, error = %e, "cannot use RSTPDN line"),
         }
+    }
+    let version = match pn532.init() {
+        Ok(version) => version,
         Err(e) => {
-            println!("Failed to get firmware version: {}", e);
-            return Err(e);
+            warn!(error = %e, "PN532 init failed, resetting");
+            pn532.recover()?
         }
+    };
+    status::set_firmware(version);
+    let ic = (version >> 16) & 0xFF;
+    let ver = (version >> 8) & 0xFF;
+    let rev = version & 0xFF;
+    info!(
+        ic = format_args!("PN5{:02X}", ic),
+        firmware = format_args!("{}.{}", ver, rev),
+        "PN532 ready, SAM configured"
+    );
+
+    match &args.command {
+        Some(Command::Write {
+            file,
+            force,
+            timeout,
+        }) => return write_card(&mut pn532, &keys, file, *force, *timeout),
+        Some(Command::Emulate { file, timeout }) => return emulate_tag(&mut pn532, file, *timeout),
+        Some(Command::Read) | Some(Command::Status { .. }) | None => {}
     }
 
-    println!("\nConfiguring SAM...");
-    pn532.sam_configuration()?;
-    println!("SAM configured");
-    println!("\nReady to read cards. Place a card near the reader...\n");
+    info!("ready to read cards");
+    // Прошивка прочитана и SAM настроен – для Type=notify сервис запущен
+    if let Err(e) = notify::notify("READY=1") {
+        warn!(error = %e, "cannot notify systemd");
+    }
+    let mut heartbeat = Heartbeat::from_env();
 
-    let mut last_uid = Vec::new();
+    let mut watchdog = Watchdog::new(args.watchdog);
+    let mut last_card = Instant::now();
+    let feedback = start_feedback(&args)?;
+    let mut presence = PresenceTracker::new(args.debounce, Duration::from_secs(args.cooldown));
+    // Обработка карт – подписчик автомата присутствия, как и любой другой
+    let events = presence.subscribe();
 
     loop {
-        match pn532.read_passive_target() {
-            Ok(uid) => {
-                if uid != last_uid {
-                    println!("Card detected! UID: ");
-                    for byte in &uid {
-                        print!("{:02X} ", byte);
-                    }
-                    if uid.len() == 4 {
-                        let key = [0xFF; 6];
-                        if pn532.authenticate_block(4, &uid, auth_key, &key)? {
-                            match pn532.read_full_data(&uid, auth_key, &key) {
-                                Ok(data) => {
-                                    println!("Read block size: {}", data.len());
-                                    println!("Read Data: {:?}", data);
-                                    let bin = hex::decode(data).unwrap();
-                                    pn532.write_to_file("/tmp/rfid_input.bin", &bin)?;
-                                }
-                                Err(e) => {
-                                    println!("Cannot read block 4: {}", e);
-                                }
-                            }
+        if let Err(e) = heartbeat.beat() {
+            warn!(error = %e, "cannot ping systemd watchdog");
+        }
+        let idle_limit = args.power_down_after.map(Duration::from_secs);
+        if idle_limit.is_some_and(|limit| last_card.elapsed() >= limit) {
+            if let Err(e) = doze(&mut pn532) {
+                warn!(error = %e, "PowerDown cycle failed");
+            }
+        }
+        // Карту на считывателе только проверяем, поле опрашиваем, когда её нет
+        let seen = match presence.current().cloned() {
+            Some(target) => pn532
+                .check_presence(&target)
+                .map(|present| present.then_some(target)),
+            None => {
+                let polled = if args.auto_poll {
+                    pn532.poll_passive_target(AUTO_POLL_WAIT_MS)
+                } else {
+                    pn532.read_passive_target()
+                };
+                match polled {
+                    Ok(target) => Ok(Some(target)),
+                    Err(Pn532Error::NoTarget) => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+        };
+        let seen = match seen {
+            Ok(seen) => {
+                watchdog.success();
+                status::poll_succeeded();
+                seen
+            }
+            // Ошибка обмена с картой – просто промах, автомат присутствия
+            // не снимет карту из-за одного сбоя
+            Err(e) if !e.is_bus_error() => {
+                debug!(error = %e, "card error");
+                None
+            }
+            // Ошибка шины ничего не говорит о карте – состояние не меняем,
+            // иначе после восстановления она прочитается повторно
+            Err(e) => {
+                warn!(error = %e, "PN532 error");
+                METRICS.bus_errors.inc();
+                if watchdog.failure() {
+                    error!(
+                        errors = args.watchdog,
+                        "bus errors in a row, resetting PN532"
+                    );
+                    METRICS.chip_resets.inc();
+                    match pn532.recover() {
+                        Ok(version) => {
+                            status::set_firmware(version);
+                            info!("PN532 recovered")
                         }
-                    } else {
-                        println!("The length of the yuid is different: {}", uid.len())
+                        Err(e) => error!(error = %e, "PN532 recovery failed"),
                     }
-                    last_uid = uid;
-                    println!();
+                } else {
+                    thread::sleep(BUS_ERROR_BACKOFF);
                 }
+                continue;
             }
-            Err(_) => {
-                if !last_uid.is_empty() {
-                    last_uid.clear();
+        };
+        if seen.is_some() {
+            last_card = Instant::now();
+        }
+        presence.observe(seen, Instant::now());
+        for event in events.try_iter() {
+            match event {
+                PresenceEvent::CardInserted(target) => {
+                    let outcome = process_card(&mut pn532, &target, &keys);
+                    info!(outcome = outcome.as_str(), "card processed");
+                    status::card_processed(outcome);
+                    if let Some(feedback) = &feedback {
+                        let _ = feedback.send(outcome);
+                    }
+                }
+                PresenceEvent::CardRemoved { uid } => {
+                    info!(uid = %hex::encode_upper(uid), "card removed")
                 }
             }
         }
-        thread::sleep(Duration::from_millis(50));
+        // С IRQ InListPassiveTarget сам ждёт карту без нагрузки на шину,
+        // при InAutoPoll опрос и так идёт с периодом чипа
+        if !pn532.uses_irq() && !args.auto_poll {
+            thread::sleep(Duration::from_millis(50));
+        }
     }
 }
@@ -0,0 +1,162 @@
+use crate::status;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use std::thread;
+use tracing::{debug, warn};
+
+/// Адрес `/metrics` по умолчанию – только локально.
+pub const DEFAULT_METRICS_ADDR: &str = "127.0.0.1:9100";
+
+/// Счётчики станции. Всё регистрируется сразу, чтобы нулевые метрики тоже
+/// попадали в выдачу.
+pub struct Metrics {
+    registry: Registry,
+    /// По типу карты (`CardType` или `unknown`).
+    pub cards_detected: IntCounterVec,
+    /// Сектора MIFARE Classic, к которым не подошёл ни один ключ.
+    pub auth_failures: IntCounterVec,
+    pub checksum_errors: IntCounter,
+    pub bus_errors: IntCounter,
+    pub chip_resets: IntCounter,
+    /// `read-ok`/`read-fail`; остальные исходы считает griphd.
+    pub card_reads: IntCounterVec,
+    /// От обнаружения карты до записи payload.
+    pub card_read_seconds: Histogram,
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let metrics = Metrics {
+            cards_detected: IntCounterVec::new(
+                Opts::new("nfc_cards_detected_total", "Cards placed on the reader"),
+                &["card_type"],
+            )
+            .unwrap(),
+            auth_failures: IntCounterVec::new(
+                Opts::new(
+                    "nfc_auth_failures_total",
+                    "MIFARE Classic sectors no known key opened",
+                ),
+                &["sector"],
+            )
+            .unwrap(),
+            checksum_errors: IntCounter::new(
+                "nfc_frame_checksum_errors_total",
+                "PN532 response frames with a bad LCS or DCS",
+            )
+            .unwrap(),
+            bus_errors: IntCounter::new(
+                "nfc_bus_errors_total",
+                "Failed exchanges with the PN532 itself",
+            )
+            .unwrap(),
+            chip_resets: IntCounter::new(
+                "nfc_chip_resets_total",
+                "PN532 recoveries triggered by the bus watchdog",
+            )
+            .unwrap(),
+            card_reads: IntCounterVec::new(
+                Opts::new("nfc_card_reads_total", "Card reads by outcome"),
+                &["outcome"],
+            )
+            .unwrap(),
+            card_read_seconds: Histogram::with_opts(
+                HistogramOpts::new("nfc_card_read_seconds", "Time to read a card payload")
+                    .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]),
+            )
+            .unwrap(),
+            registry,
+        };
+        let collectors: [Box<dyn prometheus::core::Collector>; 7] = [
+            Box::new(metrics.cards_detected.clone()),
+            Box::new(metrics.auth_failures.clone()),
+            Box::new(metrics.checksum_errors.clone()),
+            Box::new(metrics.bus_errors.clone()),
+            Box::new(metrics.chip_resets.clone()),
+            Box::new(metrics.card_reads.clone()),
+            Box::new(metrics.card_read_seconds.clone()),
+        ];
+        for collector in collectors {
+            metrics.registry.register(collector).unwrap();
+        }
+        metrics
+    }
+
+    /// Текстовый формат Prometheus.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+/// Отдаёт `/metrics` и `/status` по HTTP из отдельного потока. Возвращает адрес, на
+/// котором сервер реально слушает (для `:0` – выбранный порт).
+pub fn serve(addr: &str) -> Result<SocketAddr, Box<dyn std::error::Error + Send + Sync>> {
+    let server = tiny_http::Server::http(addr)?;
+    let local = server
+        .server_addr()
+        .to_ip()
+        .ok_or("metrics server is not on an IP socket")?;
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            debug!(url = request.url(), "metrics request");
+            let url = request.url();
+            let response = if url == "/metrics" {
+                let header =
+                    tiny_http::Header::from_bytes("Content-Type", TextEncoder::new().format_type())
+                        .unwrap();
+                tiny_http::Response::from_string(METRICS.encode()).with_header(header)
+            } else if url == "/status" || url.starts_with("/status?") {
+                let (healthy, body) = status::report(status::max_poll_age(url));
+                let header =
+                    tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap();
+                tiny_http::Response::from_string(body.to_string())
+                    .with_header(header)
+                    .with_status_code(if healthy { 200 } else { 503 })
+            } else {
+                tiny_http::Response::from_string("not found").with_status_code(404)
+            };
+            if let Err(e) = request.respond(response) {
+                warn!(error = %e, "cannot answer metrics request");
+            }
+        }
+    });
+    Ok(local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn532reader::mock::{self, SimPn532};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    #[test]
+    fn counts_checksum_errors_and_serves_them() {
+        let before = METRICS.checksum_errors.get();
+        let (mut pn532, chip) = mock::connect(SimPn532::new());
+        chip.borrow_mut().corrupt_next_response = true;
+        pn532.get_firmware_version().unwrap();
+        assert!(METRICS.checksum_errors.get() > before);
+
+        let addr = serve("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.0\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.0 200"));
+        assert!(response.contains("nfc_frame_checksum_errors_total"));
+        assert!(response.contains("nfc_card_read_seconds_bucket"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::env;
+use std::io;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::{Duration, Instant};
+
+/// sd_notify: отправляет `state` (`READY=1`, `WATCHDOG=1`, ...) в сокет из
+/// `NOTIFY_SOCKET`. Без systemd (переменной нет) – `Ok(false)`.
+pub fn notify(state: &str) -> io::Result<bool> {
+    let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let path = path.to_string_lossy().into_owned();
+    // `@` – абстрактное пространство имён Linux
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes())?,
+        None => SocketAddr::from_pathname(&path)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(true)
+}
+
+/// Интервал сторожа из `WATCHDOG_USEC`, если он включён для этого процесса.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = env::var("WATCHDOG_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+    {
+        if pid != std::process::id() {
+            return None;
+        }
+    }
+    let usec: u64 = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// `WATCHDOG=1` из главного цикла не чаще, чем раз в половину интервала
+/// сторожа. Без `WATCHDOG_USEC` ничего не отправляет.
+pub struct Heartbeat {
+    period: Option<Duration>,
+    last: Option<Instant>,
+}
+
+impl Heartbeat {
+    pub fn from_env() -> Self {
+        Heartbeat {
+            period: watchdog_interval().map(|interval| interval / 2),
+            last: None,
+        }
+    }
+
+    pub fn beat(&mut self) -> io::Result<()> {
+        let Some(period) = self.period else {
+            return Ok(());
+        };
+        if self.last.is_some_and(|last| last.elapsed() < period) {
+            return Ok(());
+        }
+        notify("WATCHDOG=1")?;
+        self.last = Some(Instant::now());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_state_and_rate_limits_watchdog() {
+        let path = env::temp_dir().join(format!("nfc_reader_notify_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        systemd
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        env::set_var("NOTIFY_SOCKET", &path);
+
+        assert!(notify("READY=1").unwrap());
+        let mut buf = [0u8; 32];
+        let len = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+
+        let mut heartbeat = Heartbeat {
+            period: Some(Duration::from_secs(60)),
+            last: None,
+        };
+        heartbeat.beat().unwrap();
+        heartbeat.beat().unwrap();
+        let len = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"WATCHDOG=1");
+        systemd.set_nonblocking(true).unwrap();
+        assert!(systemd.recv(&mut buf).is_err());
+
+        env::remove_var("NOTIFY_SOCKET");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,516 @@
+use crate::logging;
+use crate::pn532reader::constants::*;
+use crate::pn532reader::device::{Target, PN532};
+use crate::pn532reader::error::Pn532Error;
+use tracing::debug;
+
+/// Сколько байт данных PN532 принимает и отдаёт за одну InDataExchange.
+pub const MAX_EXCHANGE_DATA: usize = 262;
+/// Бит MI (More Information) в байте Tg команды и в байте статуса ответа:
+/// данные продолжаются в следующей InDataExchange.
+pub const MI_FLAG: u8 = 0x40;
+
+/// AID приложения NDEF Type 4 Tag (NFC Forum T4T). Его же выбирают телефоны
+/// с HCE-приложениями, эмулирующими NDEF-метку.
+pub const NDEF_AID: [u8; 7] = [0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01];
+pub const CC_FILE_ID: u16 = 0xE103;
+/// Идентификатор NDEF-файла, который обычно указывают в CC.
+pub const NDEF_FILE_ID: u16 = 0xE104;
+
+/// Ограничение на число GET RESPONSE подряд – защита от зацикленной карты.
+const MAX_GET_RESPONSE: usize = 64;
+
+/// Команда ISO 7816-4. Короткая форма, если данные до 255 байт и Le до 256,
+/// иначе extended (Lc и Le по два байта).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Apdu {
+    pub cla: u8,
+    pub ins: u8,
+    pub p1: u8,
+    pub p2: u8,
+    pub data: Vec<u8>,
+    /// Ожидаемая длина ответа: `None` – без данных, 256 (65536 в extended) – максимум.
+    pub le: Option<usize>,
+}
+
+impl Apdu {
+    pub fn new(cla: u8, ins: u8, p1: u8, p2: u8) -> Self {
+        Apdu {
+            cla,
+            ins,
+            p1,
+            p2,
+            data: Vec::new(),
+            le: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: &[u8]) -> Self {
+        self.data = data.to_vec();
+        self
+    }
+
+    pub fn with_le(mut self, le: usize) -> Self {
+        self.le = Some(le);
+        self
+    }
+
+    /// SELECT по имени приложения, ответ – FCI.
+    pub fn select_aid(aid: &[u8]) -> Self {
+        Apdu::new(0x00, 0xA4, 0x04, 0x00)
+            .with_data(aid)
+            .with_le(256)
+    }
+
+    /// SELECT по идентификатору файла, без FCI в ответе.
+    pub fn select_file(file_id: u16) -> Self {
+        Apdu::new(0x00, 0xA4, 0x00, 0x0C).with_data(&file_id.to_be_bytes())
+    }
+
+    pub fn read_binary(offset: u16, len: usize) -> Self {
+        let [p1, p2] = offset.to_be_bytes();
+        Apdu::new(0x00, 0xB0, p1, p2).with_le(len)
+    }
+
+    pub fn update_binary(offset: u16, data: &[u8]) -> Self {
+        let [p1, p2] = offset.to_be_bytes();
+        Apdu::new(0x00, 0xD6, p1, p2).with_data(data)
+    }
+
+    /// Разбирает команду в короткой или extended форме. Le = 0 означает
+    /// максимум (256 или 65536).
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let header = bytes.get(..4)?;
+        let mut apdu = Apdu::new(header[0], header[1], header[2], header[3]);
+        let body = &bytes[4..];
+        let le = |raw: usize, max: usize| Some(if raw == 0 { max } else { raw });
+        let (extended, rest) = match body {
+            [] => return Some(apdu),
+            [raw] => {
+                apdu.le = le(*raw as usize, 0x100);
+                return Some(apdu);
+            }
+            [0x00, hi, lo] => {
+                apdu.le = le(u16::from_be_bytes([*hi, *lo]) as usize, 0x10000);
+                return Some(apdu);
+            }
+            [0x00, hi, lo, rest @ ..] => (
+                true,
+                rest.split_at_checked(u16::from_be_bytes([*hi, *lo]) as usize)?,
+            ),
+            [n, rest @ ..] => (false, rest.split_at_checked(*n as usize)?),
+        };
+        let (data, le_field) = rest;
+        apdu.data = data.to_vec();
+        apdu.le = match (extended, le_field) {
+            (_, []) => None,
+            (false, [raw]) => le(*raw as usize, 0x100),
+            (true, [hi, lo]) => le(u16::from_be_bytes([*hi, *lo]) as usize, 0x10000),
+            _ => return None,
+        };
+        Some(apdu)
+    }
+
+    fn is_extended(&self) -> bool {
+        self.data.len() > 255 || self.le.is_some_and(|le| le > 256)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![self.cla, self.ins, self.p1, self.p2];
+        let extended = self.is_extended();
+        if !self.data.is_empty() {
+            if extended {
+                out.push(0x00);
+                out.extend_from_slice(&(self.data.len() as u16).to_be_bytes());
+            } else {
+                out.push(self.data.len() as u8);
+            }
+            out.extend_from_slice(&self.data);
+        }
+        if let Some(le) = self.le {
+            // Максимальные 256/65536 кодируются нулями
+            if extended {
+                if self.data.is_empty() {
+                    out.push(0x00);
+                }
+                out.extend_from_slice(&((le % 0x10000) as u16).to_be_bytes());
+            } else {
+                out.push((le % 0x100) as u8);
+            }
+        }
+        out
+    }
+}
+
+/// Ответ карты: данные и слово состояния SW1 SW2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApduResponse {
+    pub data: Vec<u8>,
+    pub sw: u16,
+}
+
+impl ApduResponse {
+    pub fn parse(raw: &[u8]) -> Result<Self, Pn532Error> {
+        if raw.len() < 2 {
+            return Err(Pn532Error::InvalidResponse(
+                "APDU response without status word",
+            ));
+        }
+        let (data, sw) = raw.split_at(raw.len() - 2);
+        Ok(ApduResponse {
+            data: data.to_vec(),
+            sw: u16::from_be_bytes([sw[0], sw[1]]),
+        })
+    }
+
+    pub fn sw1(&self) -> u8 {
+        (self.sw >> 8) as u8
+    }
+
+    pub fn sw2(&self) -> u8 {
+        self.sw as u8
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.sw == 0x9000
+    }
+
+    /// Данные ответа, если SW = 9000, иначе `ApduStatus`.
+    pub fn into_data(self) -> Result<Vec<u8>, Pn532Error> {
+        if self.is_success() {
+            Ok(self.data)
+        } else {
+            Err(Pn532Error::ApduStatus(self.sw))
+        }
+    }
+}
+
+/// Расшифровка SW1 SW2 (ISO 7816-4, раздел 5.6).
+pub fn status_word_description(sw: u16) -> &'static str {
+    match sw {
+        0x9000 => "success",
+        0x6281 => "part of returned data may be corrupted",
+        0x6282 => "end of file reached before reading Le bytes",
+        0x6283 => "selected file invalidated",
+        0x6700 => "wrong length",
+        0x6881 => "logical channel not supported",
+        0x6882 => "secure messaging not supported",
+        0x6982 => "security status not satisfied",
+        0x6983 => "authentication method blocked",
+        0x6985 => "conditions of use not satisfied",
+        0x6986 => "command not allowed, no current EF",
+        0x6A80 => "incorrect parameters in the data field",
+        0x6A81 => "function not supported",
+        0x6A82 => "file or application not found",
+        0x6A86 => "incorrect parameters P1-P2",
+        0x6A87 => "Lc inconsistent with P1-P2",
+        0x6A88 => "referenced data not found",
+        0x6B00 => "wrong parameters, offset outside the EF",
+        0x6D00 => "instruction not supported",
+        0x6E00 => "class not supported",
+        0x6F00 => "no precise diagnosis",
+        _ => match sw >> 8 {
+            0x61 => "more data available with GET RESPONSE",
+            0x63 => "warning, non-volatile memory changed",
+            0x6C => "wrong Le, exact length in SW2",
+            _ => "unknown status word",
+        },
+    }
+}
+
+/// CC-файл Type 4 метки (NFC Forum T4T, 5.1): размеры APDU и NDEF-файл.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Type4CapabilityContainer {
+    pub version: u8,
+    /// MLe – сколько байт карта отдаёт за один READ BINARY.
+    pub max_read: u16,
+    /// MLc – сколько байт принимает за один UPDATE BINARY.
+    pub max_write: u16,
+    pub ndef_file_id: u16,
+    pub max_ndef_size: u16,
+    pub read_access: u8,
+    pub write_access: u8,
+}
+
+impl Type4CapabilityContainer {
+    /// CCLEN(2) | version | MLe(2) | MLc(2) | NDEF File Control TLV: 04 06 ID(2) size(2) R W
+    pub fn parse(cc: &[u8]) -> Result<Self, Pn532Error> {
+        if cc.len() < 15 {
+            return Err(Pn532Error::InvalidResponse("CC file too short"));
+        }
+        if cc[7] != 0x04 || cc[8] < 6 {
+            return Err(Pn532Error::InvalidResponse(
+                "no NDEF File Control TLV in CC",
+            ));
+        }
+        let word = |i: usize| u16::from_be_bytes([cc[i], cc[i + 1]]);
+        Ok(Type4CapabilityContainer {
+            version: cc[2],
+            max_read: word(3),
+            max_write: word(5),
+            ndef_file_id: word(9),
+            max_ndef_size: word(11),
+            read_access: cc[13],
+            write_access: cc[14],
+        })
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.read_access == 0x00
+    }
+}
+
+/// Le для повтора по 61XX/6CXX: SW2 = 0 означает 256 байт.
+fn le_from_sw2(sw2: u8) -> usize {
+    if sw2 == 0 {
+        256
+    } else {
+        sw2 as usize
+    }
+}
+
+impl PN532 {
+    /// InDataExchange с произвольным объёмом данных. Всё, что не влезает в
+    /// `MAX_EXCHANGE_DATA`, уходит несколькими командами с битом MI в Tg;
+    /// ответ с битом MI в статусе дочитывается командами без данных.
+    pub fn data_exchange(&mut self, tg: u8, data: &[u8]) -> Result<Vec<u8>, Pn532Error> {
+        let mut offset = 0;
+        let (mut status, mut chunk) = loop {
+            let end = (offset + MAX_EXCHANGE_DATA).min(data.len());
+            let more = end < data.len();
+            let tg = if more { tg | MI_FLAG } else { tg };
+            let reply = self.data_exchange_frame(tg, &data[offset..end])?;
+            if !more {
+                break reply;
+            }
+            offset = end;
+        };
+        let mut reply = Vec::new();
+        loop {
+            reply.append(&mut chunk);
+            if status & MI_FLAG == 0 {
+                return Ok(reply);
+            }
+            (status, chunk) = self.data_exchange_frame(tg, &[])?;
+        }
+    }
+
+    /// Одна InDataExchange: байт статуса (с флагами MI/NAD) и данные после него.
+    fn data_exchange_frame(&mut self, tg: u8, data: &[u8]) -> Result<(u8, Vec<u8>), Pn532Error> {
+        let mut command = vec![PN532_COMMAND_INDATAEXCHANGE, tg];
+        command.extend_from_slice(data);
+        self.write_command(&command)?;
+        self.read_ack()?;
+        let response = self.read_response(PN532_COMMAND_INDATAEXCHANGE, MAX_EXCHANGE_DATA + 1)?;
+        match response.split_first() {
+            Some((&status, _)) if status & 0x3F != 0 => Err(Pn532Error::CardStatus(status)),
+            Some((&status, rest)) => Ok((status, rest.to_vec())),
+            None => Err(Pn532Error::InvalidResponse("empty response")),
+        }
+    }
+
+    /// APDU карте ISO14443-4. Протокол T=CL (I-блоки, нумерацию, WTX и цепочки
+    /// по FSC карты) PN532 ведёт сам, хосту остаются цепочки InDataExchange
+    /// и слово состояния: 61XX дочитывается GET RESPONSE, на 6CXX команда
+    /// повторяется с Le из SW2.
+    pub fn exchange_apdu(
+        &mut self,
+        target: &Target,
+        apdu: &Apdu,
+    ) -> Result<ApduResponse, Pn532Error> {
+        if !target.is_iso14443_4() {
+            return Err(Pn532Error::Unsupported(
+                "target does not support ISO14443-4",
+            ));
+        }
+        debug!(apdu = %logging::bytes(&apdu.to_bytes()), "C-APDU");
+        let mut response = ApduResponse::parse(&self.data_exchange(target.tg, &apdu.to_bytes())?)?;
+        if response.sw1() == 0x6C {
+            let retry = apdu.clone().with_le(le_from_sw2(response.sw2()));
+            response = ApduResponse::parse(&self.data_exchange(target.tg, &retry.to_bytes())?)?;
+        }
+        let mut data = std::mem::take(&mut response.data);
+        let mut rounds = 0;
+        while response.sw1() == 0x61 {
+            rounds += 1;
+            if rounds > MAX_GET_RESPONSE {
+                return Err(Pn532Error::InvalidResponse("too many GET RESPONSE rounds"));
+            }
+            let get_response =
+                Apdu::new(apdu.cla, 0xC0, 0x00, 0x00).with_le(le_from_sw2(response.sw2()));
+            response =
+                ApduResponse::parse(&self.data_exchange(target.tg, &get_response.to_bytes())?)?;
+            data.append(&mut response.data);
+        }
+        debug!(
+            len = data.len(),
+            sw = format_args!("{:04X}", response.sw),
+            "R-APDU"
+        );
+        Ok(ApduResponse {
+            data,
+            sw: response.sw,
+        })
+    }
+
+    /// SELECT AID; возвращает FCI.
+    pub fn select_aid(&mut self, target: &Target, aid: &[u8]) -> Result<Vec<u8>, Pn532Error> {
+        self.exchange_apdu(target, &Apdu::select_aid(aid))?
+            .into_data()
+    }
+
+    fn read_binary(
+        &mut self,
+        target: &Target,
+        offset: u16,
+        len: usize,
+    ) -> Result<Vec<u8>, Pn532Error> {
+        self.exchange_apdu(target, &Apdu::read_binary(offset, len))?
+            .into_data()
+    }
+
+    /// Читает NDEF-сообщение Type 4 метки: SELECT приложения NDEF, CC-файл,
+    /// затем NDEF-файл – NLEN и само сообщение кусками не больше MLe.
+    /// Так же читаются телефоны с HCE-приложением, эмулирующим NDEF-метку.
+    pub fn read_type4_ndef(&mut self, target: &Target) -> Result<Vec<u8>, Pn532Error> {
+        self.select_aid(target, &NDEF_AID)?;
+        self.exchange_apdu(target, &Apdu::select_file(CC_FILE_ID))?
+            .into_data()?;
+        let cc = Type4CapabilityContainer::parse(&self.read_binary(target, 0, 15)?)?;
+        debug!(?cc, "Type 4 CC");
+        if !cc.is_readable() {
+            return Err(Pn532Error::Unsupported("NDEF file is read-protected"));
+        }
+        self.exchange_apdu(target, &Apdu::select_file(cc.ndef_file_id))?
+            .into_data()?;
+
+        let nlen = self.read_binary(target, 0, 2)?;
+        let [hi, lo] = nlen[..] else {
+            return Err(Pn532Error::InvalidResponse("NLEN must be 2 bytes"));
+        };
+        let nlen = u16::from_be_bytes([hi, lo]) as usize;
+        if nlen + 2 > cc.max_ndef_size as usize {
+            return Err(Pn532Error::InvalidResponse("NLEN exceeds NDEF file size"));
+        }
+        // READ BINARY в короткой форме – не больше 255 байт за раз
+        let chunk = (cc.max_read as usize).clamp(1, 255);
+        let mut message = Vec::with_capacity(nlen);
+        while message.len() < nlen {
+            let offset = (2 + message.len()) as u16;
+            let part = self.read_binary(target, offset, chunk.min(nlen - message.len()))?;
+            if part.is_empty() {
+                return Err(Pn532Error::InvalidResponse("empty READ BINARY response"));
+            }
+            message.extend_from_slice(&part);
+        }
+        message.truncate(nlen);
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn532reader::mock::{self, SimPn532, Type4Tag, VirtualTag};
+    use nfc_format::ndef::{Message, Record, CTF_MIME_TYPE};
+
+    #[test]
+    fn encodes_short_and_extended_apdus() {
+        assert_eq!(
+            Apdu::select_aid(&NDEF_AID).to_bytes(),
+            [0x00, 0xA4, 0x04, 0x00, 0x07, 0xD2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01, 0x00]
+        );
+        assert_eq!(
+            Apdu::read_binary(0x0102, 256).to_bytes(),
+            [0x00, 0xB0, 0x01, 0x02, 0x00]
+        );
+        assert_eq!(
+            Apdu::read_binary(0, 600).to_bytes(),
+            [0x00, 0xB0, 0x00, 0x00, 0x00, 0x02, 0x58]
+        );
+        let update = Apdu::update_binary(2, &[0xAB; 300]).with_le(256).to_bytes();
+        assert_eq!(update[4..7], [0x00, 0x01, 0x2C]);
+        assert_eq!(update[307..], [0x01, 0x00]);
+
+        let response = ApduResponse::parse(&[0x01, 0x6A, 0x82]).unwrap();
+        assert_eq!(response.data, [0x01]);
+        assert_eq!(
+            response.into_data().unwrap_err().to_string(),
+            "card returned SW 6A82: file or application not found"
+        );
+        assert!(ApduResponse::parse(&[0x90]).is_err());
+        assert_eq!(
+            status_word_description(0x6110),
+            "more data available with GET RESPONSE"
+        );
+    }
+
+    #[test]
+    fn reads_type4_ndef_in_chunks() {
+        let message = Message::new(vec![Record::mime(CTF_MIME_TYPE, &[0x5A; 600])])
+            .to_bytes()
+            .unwrap();
+        let tag = Type4Tag::new([0x08, 1, 2, 3, 4, 5, 6], &message, 1024);
+        let (mut pn532, chip) = mock::connect(SimPn532::with_tag(VirtualTag::Type4(tag)));
+        let target = pn532.read_passive_target().unwrap();
+        assert!(target.is_iso14443_4());
+        assert!(target.ats.is_some());
+
+        assert_eq!(pn532.read_type4_ndef(&target).unwrap(), message);
+        // MLe = 0x80: NLEN и 5 кусков сообщения
+        let reads = chip
+            .borrow()
+            .received
+            .iter()
+            .filter(|command| command.get(3) == Some(&0xB0))
+            .count();
+        assert_eq!(reads, 1 + 1 + 5);
+
+        assert!(matches!(
+            pn532.select_aid(&target, &[0xF0, 0x01, 0x02, 0x03]),
+            Err(Pn532Error::ApduStatus(0x6A82))
+        ));
+    }
+
+    #[test]
+    fn chains_long_apdus_with_mi() {
+        let mut tag = Type4Tag::new([0x08, 1, 2, 3, 4, 5, 6], &[], 1024);
+        // Extended READ BINARY за один раз
+        tag.emulator.max_read = 1024;
+        let (mut pn532, chip) = mock::connect(SimPn532::with_tag(VirtualTag::Type4(tag)));
+        let target = pn532.read_passive_target().unwrap();
+        pn532.select_aid(&target, &NDEF_AID).unwrap();
+        pn532
+            .exchange_apdu(&target, &Apdu::select_file(NDEF_FILE_ID))
+            .unwrap();
+
+        let data: Vec<u8> = (0..600).map(|i| i as u8).collect();
+        let response = pn532
+            .exchange_apdu(&target, &Apdu::update_binary(0, &data))
+            .unwrap();
+        assert!(response.is_success());
+        // 600 байт APDU + заголовок – три InDataExchange, первые две с MI
+        let tgs: Vec<u8> = chip.borrow().received[chip.borrow().received.len() - 3..]
+            .iter()
+            .map(|command| command[1])
+            .collect();
+        assert_eq!(tgs, [0x41, 0x41, 0x01]);
+
+        let response = pn532
+            .exchange_apdu(&target, &Apdu::read_binary(0, 600))
+            .unwrap();
+        assert_eq!(response.data, data);
+        assert_eq!(response.sw, 0x9000);
+    }
+
+    #[test]
+    fn rejects_tags_without_iso14443_4() {
+        let tag = mock::ClassicTag::blank_1k([1, 2, 3, 4]);
+        let (mut pn532, _) = mock::connect(SimPn532::with_tag(VirtualTag::Classic(tag)));
+        let target = pn532.read_passive_target().unwrap();
+        assert!(matches!(
+            pn532.read_type4_ndef(&target),
+            Err(Pn532Error::Unsupported(_))
+        ));
+    }
+}
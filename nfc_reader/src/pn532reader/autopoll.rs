@@ -0,0 +1,168 @@
+use crate::pn532reader::constants::*;
+use crate::pn532reader::device::PN532;
+use crate::pn532reader::error::Pn532Error;
+use crate::pn532reader::target::parse_auto_poll;
+pub use crate::pn532reader::target::{PollType, Target};
+
+/// PollNr = 0xFF – опрашивать, пока карта не появится.
+pub const POLL_FOREVER: u8 = 0xFF;
+/// Единица Period в InAutoPoll.
+const PERIOD_UNIT_MS: u32 = 150;
+/// Запас сверх расчётного времени опроса на обмен по шине.
+const POLL_MARGIN_MS: u32 = 500;
+
+/// Параметры InAutoPoll (UM0701-02, 7.3.13): сколько раз пройти по списку
+/// типов и сколько ждать на каждом типе.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoPoll {
+    /// 1..=0xFE или `POLL_FOREVER`.
+    pub poll_count: u8,
+    /// Пауза на каждом типе в единицах по 150 мс, 1..=15.
+    pub period: u8,
+    /// От 1 до 15 типов, опрашиваются по порядку.
+    pub types: Vec<PollType>,
+}
+
+impl AutoPoll {
+    /// Бесконечный опрос с периодом 300 мс.
+    pub fn new(types: &[PollType]) -> Self {
+        AutoPoll {
+            poll_count: POLL_FOREVER,
+            period: 2,
+            types: types.to_vec(),
+        }
+    }
+
+    pub fn with_poll_count(mut self, poll_count: u8) -> Self {
+        self.poll_count = poll_count.max(1);
+        self
+    }
+
+    pub fn with_period(mut self, period: u8) -> Self {
+        self.period = period.clamp(1, 15);
+        self
+    }
+
+    /// Сколько чип будет опрашивать поле, прежде чем ответит «карт нет».
+    /// `None` – бесконечный опрос.
+    pub fn duration_ms(&self) -> Option<u32> {
+        if self.poll_count == POLL_FOREVER {
+            return None;
+        }
+        Some(self.poll_count as u32 * self.types.len() as u32 * self.period as u32 * PERIOD_UNIT_MS)
+    }
+}
+
+impl PN532 {
+    /// InAutoPoll: поле опрашивает сам чип, хост ждёт ответа, пока карта не
+    /// появится. Пустой список – опрос закончился без карт или истёк
+    /// `timeout_ms` (тогда команда отменяется ACK-ом). Найденные цели
+    /// активированы, как после InListPassiveTarget.
+    pub fn in_auto_poll(
+        &mut self,
+        config: &AutoPoll,
+        timeout_ms: u32,
+    ) -> Result<Vec<(PollType, Target)>, Pn532Error> {
+        if !(1..=15).contains(&config.types.len()) {
+            return Err(Pn532Error::Unsupported(
+                "InAutoPoll takes 1 to 15 target types",
+            ));
+        }
+        let mut command = vec![
+            PN532_COMMAND_INAUTOPOLL,
+            config.poll_count,
+            config.period.clamp(1, 15),
+        ];
+        command.extend(config.types.iter().map(PollType::code));
+        self.write_command(&command)?;
+        self.read_ack()?;
+        let wait_ms = match config.duration_ms() {
+            Some(duration) => timeout_ms.min(duration + POLL_MARGIN_MS),
+            None => timeout_ms,
+        };
+        match self.read_response_within(PN532_COMMAND_INAUTOPOLL, 255, wait_ms) {
+            Ok(response) => parse_auto_poll(&response),
+            Err(Pn532Error::Timeout) => {
+                // ACK от хоста останавливает опрос
+                self.cancel_command()?;
+                Ok(Vec::new())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Одна карта type A через InAutoPoll. Нет карты за `timeout_ms` – `NoTarget`.
+    pub fn poll_passive_target(&mut self, timeout_ms: u32) -> Result<Target, Pn532Error> {
+        self.in_auto_poll(&AutoPoll::new(&[PollType::GenericPassive106]), timeout_ms)?
+            .into_iter()
+            .map(|(_, target)| target)
+            .next()
+            .ok_or(Pn532Error::NoTarget)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn532reader::frame::build_frame;
+    use crate::pn532reader::mock::{self, ClassicTag, SimPn532, Type4Tag, VirtualTag};
+    use crate::pn532reader::target::Modulation;
+
+    #[test]
+    fn auto_poll_returns_typed_targets() {
+        let (mut pn532, chip) = mock::connect(SimPn532::with_tag(VirtualTag::Classic(
+            ClassicTag::blank_1k([0xDE, 0xAD, 0xBE, 0xEF]),
+        )));
+        chip.borrow_mut().second_tag = Some(VirtualTag::Type4(Type4Tag::new(
+            [0x08, 1, 2, 3, 4, 5, 6],
+            &[],
+            256,
+        )));
+
+        // Первым опрашивается ISO14443-4A – MIFARE Classic в ответ не попадает
+        let config = AutoPoll::new(&[PollType::Iso14443_4A, PollType::Mifare]).with_poll_count(1);
+        let targets = pn532.in_auto_poll(&config, 1000).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].0, PollType::Iso14443_4A);
+        assert_eq!(targets[0].1.modulation, Modulation::Iso14443A);
+        assert!(targets[0].1.is_iso14443_4());
+        assert_eq!(
+            chip.borrow().received.last().unwrap(),
+            &[PN532_COMMAND_INAUTOPOLL, 0x01, 0x02, 0x20, 0x10]
+        );
+
+        let targets = pn532
+            .in_auto_poll(&AutoPoll::new(&[PollType::Mifare]), 1000)
+            .unwrap();
+        assert_eq!(targets[0].1.uid, [0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(pn532.poll_passive_target(1000).unwrap().tg, 1);
+    }
+
+    #[test]
+    fn auto_poll_without_cards() {
+        let (mut pn532, chip) = mock::connect(SimPn532::new());
+        let config = AutoPoll::new(&[PollType::GenericPassive106]).with_poll_count(3);
+        assert_eq!(config.duration_ms(), Some(900));
+        assert!(pn532.in_auto_poll(&config, 5000).unwrap().is_empty());
+
+        // Бесконечный опрос отменяется по таймауту, чип снова принимает команды
+        assert!(matches!(
+            pn532.poll_passive_target(20),
+            Err(Pn532Error::NoTarget)
+        ));
+        assert_eq!(pn532.get_firmware_version().unwrap(), 0x320106);
+
+        // Ответ разминулся с отменой – он вычитывается, а не достаётся
+        // следующей команде вместо ACK
+        chip.borrow_mut().response_in_flight = Some(build_frame(
+            PN532_PN532TOHOST,
+            &[PN532_COMMAND_INAUTOPOLL + 1, 0x00],
+        ));
+        assert!(pn532.poll_passive_target(20).is_err());
+        assert!(!pn532.wait_ready(0).unwrap());
+        assert_eq!(pn532.get_firmware_version().unwrap(), 0x320106);
+
+        assert!(pn532.in_auto_poll(&AutoPoll::new(&[]), 20).is_err());
+        assert_eq!(chip.borrow().received.len(), 5);
+    }
+}
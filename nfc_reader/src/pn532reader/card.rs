@@ -0,0 +1,320 @@
+use crate::pn532reader::device::{Target, PN532};
+use crate::pn532reader::error::Pn532Error;
+use crate::pn532reader::ntag::{NtagModel, NTAG_USER_START_PAGE};
+use crate::pn532reader::target::Modulation;
+use tracing::debug;
+
+/// Тип карты по ATQA/SAK и, для Type 2, по GET_VERSION.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardType {
+    MifareMini,
+    MifareClassic1K,
+    MifareClassic4K,
+    MifareUltralight,
+    MifareUltralightC,
+    Ntag(NtagModel),
+    Desfire,
+    /// Любая другая карта ISO14443-4 (банковские, SmartMX без эмуляции Classic...).
+    IsoDep,
+    Unknown,
+}
+
+/// Сектор MIFARE Classic: 4 блока в секторах 0-31, 16 блоков в секторах 32-39 (4K).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClassicSector {
+    pub index: u8,
+    pub first_block: u8,
+    pub block_count: u8,
+}
+
+impl ClassicSector {
+    pub fn trailer(&self) -> u8 {
+        self.first_block + (self.block_count - 1)
+    }
+
+    pub fn blocks(&self) -> std::ops::RangeInclusive<u8> {
+        self.first_block..=self.trailer()
+    }
+
+    pub fn data_blocks(&self) -> std::ops::Range<u8> {
+        self.first_block..self.trailer()
+    }
+}
+
+/// Сектора MIFARE Classic с `count` секторами (5 – Mini, 16 – 1K, 40 – 4K).
+pub fn classic_sectors(count: u8) -> Vec<ClassicSector> {
+    (0..count)
+        .map(|index| {
+            if index < 32 {
+                ClassicSector {
+                    index,
+                    first_block: index * 4,
+                    block_count: 4,
+                }
+            } else {
+                ClassicSector {
+                    index,
+                    first_block: 128 + (index - 32) * 16,
+                    block_count: 16,
+                }
+            }
+        })
+        .collect()
+}
+
+/// Номер сектора, в котором лежит блок.
+pub fn classic_sector_of(block: u8) -> u8 {
+    if block < 128 {
+        block / 4
+    } else {
+        32 + (block - 128) / 16
+    }
+}
+
+/// Как устроена память карты и как её читать.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryMap {
+    /// Сектора с аутентификацией, блоки по 16 байт.
+    Classic(Vec<ClassicSector>),
+    /// Страницы по 4 байта (Type 2): всего `total_pages`, пользовательские –
+    /// с `user_start` по `user_end` включительно.
+    Pages {
+        total_pages: u8,
+        user_start: u8,
+        user_end: u8,
+    },
+    /// Память доступна только через APDU.
+    Application,
+}
+
+impl MemoryMap {
+    /// Блоки NDEF-области MIFARE Classic: с сектора 1 (в секторе 0 –
+    /// данные производителя и MAD), без трейлеров.
+    pub fn classic_data_blocks(&self) -> Vec<u8> {
+        match self {
+            MemoryMap::Classic(sectors) => sectors
+                .iter()
+                .skip(1)
+                .flat_map(|sector| sector.data_blocks())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl CardType {
+    /// Грубая классификация по ATQA/SAK (NXP AN10833). Type 2 метки без
+    /// GET_VERSION не различить – для них возвращается `MifareUltralight`.
+    pub fn from_target(target: &Target) -> Self {
+        if target.modulation != Modulation::Iso14443A {
+            return if target.is_iso14443_4() {
+                CardType::IsoDep
+            } else {
+                CardType::Unknown
+            };
+        }
+        match target.sel_res {
+            0x09 => CardType::MifareMini,
+            // 0x28/0x38 – SmartMX с эмуляцией Classic, 0x88 – Infineon 1K
+            0x08 | 0x28 | 0x88 => CardType::MifareClassic1K,
+            0x18 | 0x38 => CardType::MifareClassic4K,
+            0x00 if target.sens_res == 0x0044 => CardType::MifareUltralight,
+            0x20 if matches!(target.sens_res, 0x0344 | 0x0304) => CardType::Desfire,
+            sak if sak & 0x20 != 0 => CardType::IsoDep,
+            _ => CardType::Unknown,
+        }
+    }
+
+    pub fn is_type2(&self) -> bool {
+        matches!(
+            self,
+            CardType::MifareUltralight | CardType::MifareUltralightC | CardType::Ntag(_)
+        )
+    }
+
+    pub fn memory_map(&self) -> MemoryMap {
+        let pages = |total_pages: u8, user_end: u8| MemoryMap::Pages {
+            total_pages,
+            user_start: NTAG_USER_START_PAGE,
+            user_end,
+        };
+        match self {
+            CardType::MifareMini => MemoryMap::Classic(classic_sectors(5)),
+            CardType::MifareClassic1K => MemoryMap::Classic(classic_sectors(16)),
+            CardType::MifareClassic4K => MemoryMap::Classic(classic_sectors(40)),
+            CardType::MifareUltralight => pages(16, 15),
+            // Страницы 0x28-0x2F – lock-байты, счётчик, настройки и ключ 3DES
+            CardType::MifareUltralightC => pages(48, 0x27),
+            CardType::Ntag(model) => pages(model.total_pages(), model.last_user_page()),
+            CardType::Desfire | CardType::IsoDep | CardType::Unknown => MemoryMap::Application,
+        }
+    }
+}
+
+/// Первая страница, которой нет у обычного Ultralight (16 страниц).
+const ULTRALIGHT_C_PROBE_PAGE: u8 = 0x10;
+
+impl PN532 {
+    /// Уточняет тип Type 2 метки: NTAG и Ultralight EV1 отвечают на GET_VERSION,
+    /// Ultralight C – нет, но, в отличие от Ultralight, читает страницы после 15-й.
+    /// После неудачной команды метка уходит в IDLE, поэтому её активируем заново
+    /// по UID: пропала – `NoTarget`, а не тип другой карты из поля.
+    pub fn identify_card(&mut self, target: &Target) -> Result<CardType, Pn532Error> {
+        let card = CardType::from_target(target);
+        if !card.is_type2() {
+            return Ok(card);
+        }
+        match self.ntag_get_version() {
+            Ok(version) => {
+                debug!(version = %hex::encode_upper(version), "GET_VERSION");
+                // Ultralight EV1 и прочие – читаем как обычный Ultralight
+                return Ok(NtagModel::from_version(&version)
+                    .map(CardType::Ntag)
+                    .unwrap_or(CardType::MifareUltralight));
+            }
+            Err(Pn532Error::CardStatus(_)) => {
+                self.select_by_uid(&target.uid)?;
+            }
+            Err(e) => return Err(e),
+        }
+        match self.read_block(ULTRALIGHT_C_PROBE_PAGE) {
+            Ok(_) => Ok(CardType::MifareUltralightC),
+            Err(Pn532Error::CardStatus(_)) => {
+                self.select_by_uid(&target.uid)?;
+                Ok(CardType::MifareUltralight)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn532reader::constants::PN532_COMMAND_INLISTPASSIVETARGET;
+    use crate::pn532reader::mock::{self, ClassicTag, NtagTag, SimPn532, VirtualTag};
+
+    fn target(sens_res: u16, sel_res: u8) -> Target {
+        Target {
+            tg: 1,
+            modulation: Modulation::Iso14443A,
+            sens_res,
+            sel_res,
+            uid: vec![0; 4],
+            ats: None,
+            target_data: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn classifies_by_atqa_and_sak() {
+        assert_eq!(
+            CardType::from_target(&target(0x0004, 0x09)),
+            CardType::MifareMini
+        );
+        assert_eq!(
+            CardType::from_target(&target(0x0004, 0x08)),
+            CardType::MifareClassic1K
+        );
+        assert_eq!(
+            CardType::from_target(&target(0x0002, 0x18)),
+            CardType::MifareClassic4K
+        );
+        assert_eq!(
+            CardType::from_target(&target(0x0044, 0x00)),
+            CardType::MifareUltralight
+        );
+        assert_eq!(
+            CardType::from_target(&target(0x0344, 0x20)),
+            CardType::Desfire
+        );
+        assert_eq!(
+            CardType::from_target(&target(0x0004, 0x20)),
+            CardType::IsoDep
+        );
+        assert_eq!(
+            CardType::from_target(&target(0x0004, 0x01)),
+            CardType::Unknown
+        );
+    }
+
+    #[test]
+    fn memory_maps() {
+        let MemoryMap::Classic(sectors) = CardType::MifareClassic4K.memory_map() else {
+            panic!("4K is a Classic card");
+        };
+        assert_eq!(sectors.len(), 40);
+        assert_eq!(sectors[31].trailer(), 127);
+        assert_eq!(sectors[32].first_block, 128);
+        assert_eq!(sectors[39].trailer(), 255);
+        assert_eq!(sectors[39].data_blocks().count(), 15);
+        assert_eq!(classic_sector_of(127), 31);
+        assert_eq!(classic_sector_of(200), 36);
+
+        // 1K: 15 секторов по 3 блока данных
+        assert_eq!(
+            CardType::MifareClassic1K
+                .memory_map()
+                .classic_data_blocks()
+                .len(),
+            45
+        );
+        assert_eq!(
+            CardType::Ntag(NtagModel::Ntag215).memory_map(),
+            MemoryMap::Pages {
+                total_pages: 135,
+                user_start: 4,
+                user_end: 129
+            }
+        );
+    }
+
+    #[test]
+    fn identifies_type2_with_get_version() {
+        let tag = NtagTag::blank(NtagModel::Ntag216, [0x04, 1, 2, 3, 4, 5, 6]);
+        let (mut pn532, _) = mock::connect(SimPn532::with_tag(VirtualTag::Ntag(tag)));
+        let target = pn532.read_passive_target().unwrap();
+        assert_eq!(
+            pn532.identify_card(&target).unwrap(),
+            CardType::Ntag(NtagModel::Ntag216)
+        );
+
+        let tag = ClassicTag::from_bytes(vec![0; 4096]).unwrap();
+        let (mut pn532, _) = mock::connect(SimPn532::with_tag(VirtualTag::Classic(tag)));
+        let target = pn532.read_passive_target().unwrap();
+        assert_eq!(
+            pn532.identify_card(&target).unwrap(),
+            CardType::MifareClassic4K
+        );
+    }
+
+    #[test]
+    fn reselects_the_same_card_after_get_version_nak() {
+        const UID: [u8; 7] = [0x04, 1, 2, 3, 4, 5, 6];
+        let mut tag = NtagTag::blank(NtagModel::Ntag216, UID);
+        tag.lacks_get_version = true;
+        let (mut pn532, chip) = mock::connect(SimPn532::with_tag(VirtualTag::Ntag(tag.clone())));
+        let target = pn532.read_passive_target().unwrap();
+        assert_eq!(
+            pn532.identify_card(&target).unwrap(),
+            CardType::MifareUltralightC
+        );
+        let mut select = vec![PN532_COMMAND_INLISTPASSIVETARGET, 0x01, 0x00];
+        select.extend_from_slice(&UID);
+        assert!(chip.borrow().received.contains(&select));
+
+        // Метку убрали после NAK, в поле осталась другая – её тип не годится
+        tag.leaves_field_on_nak = true;
+        chip.borrow_mut().tag = Some(VirtualTag::Ntag(tag));
+        chip.borrow_mut().second_tag = Some(VirtualTag::Ntag(NtagTag::blank(
+            NtagModel::Ntag213,
+            [0x04, 9, 9, 9, 9, 9, 9],
+        )));
+        let target = pn532.read_passive_target().unwrap();
+        assert_eq!(target.uid, UID);
+        assert!(matches!(
+            pn532.identify_card(&target),
+            Err(Pn532Error::NoTarget)
+        ));
+    }
+}
@@ -1,127 +1,120 @@
 use super::constants::*;
+use crate::logging;
+use crate::pn532reader::apdu::MAX_EXCHANGE_DATA;
 use crate::pn532reader::device::PN532;
-use embedded_hal::i2c::I2c;
-use std::fs::OpenOptions;
-use std::io::Write;
+use crate::pn532reader::error::Pn532Error;
+use crate::pn532reader::frame::{self, Frame, ACK_FRAME};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
 use std::thread;
 use std::time::Duration;
+use tracing::{debug, trace};
 
-impl PN532 {
-    pub(crate) fn wait_ready(
-        &mut self,
-        timeout_ms: u32,
-    ) -> Result<bool, Box<dyn std::error::Error>> {
-        let start = std::time::Instant::now();
-
-        loop {
-            let mut buffer = [0u8; 1];
-            match self.i2c.read(self.address, &mut buffer) {
-                Ok(_) => {
-                    // "Bit 0 of the status byte indicates if the PN532 is ready to be read (1: ready, 0: not ready)."
-                    if buffer[0] & 0x01 == 0x01 {
-                        return Ok(true);
-                    }
-                }
-                Err(_) => {}
-            }
+/// Сколько ждать ответа, разминувшегося с отменой команды.
+const CANCEL_DRAIN_MS: u32 = 20;
 
-            if start.elapsed().as_millis() > timeout_ms as u128 {
-                return Ok(false);
-            }
-            thread::sleep(Duration::from_millis(5));
-        }
+impl PN532 {
+    pub(crate) fn wait_ready(&mut self, timeout_ms: u32) -> Result<bool, Pn532Error> {
+        self.transport.wait_ready(timeout_ms)
     }
 
-    pub(crate) fn write_command(
-        &mut self,
-        command: &[u8],
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // LEN — длина данных (включая TFI и command) LEN = длина(TFI + DATA)
-        // LCS — контрольная сумма длины (Length Checksum)
-        // TFI считается частью полезной нагрузки (Data Packet)
-        let len = command.len() as u8 + 1;
-        // LEN + LCS = 0 (по модулю 256)
-        // !0x05 = 0xFA  // побитовая инверсия: 1111_1010
-        //  +1     = 0xFB
-        //  & 0xFF = 0xFB
-        let lcs = (!len + 1) & 0xFF;
-        let mut frame = vec![
-            PN532_PREAMBLE,
-            PN532_STARTCODE1,
-            PN532_STARTCODE2,
-            len,
-            lcs,
-            PN532_HOSTTOPN532,
-        ];
-        frame.extend_from_slice(command);
-        let mut checksum = PN532_HOSTTOPN532;
-        // вычисляем контрольную сумму данных (DCS)
+    pub(crate) fn write_command(&mut self, command: &[u8]) -> Result<(), Pn532Error> {
         // PRE | START1 | START2 | LEN | LCS |  TFI |  DATA...   | DCS | POST
-        for &byte in command {
-            checksum = checksum.wrapping_add(byte);
-        }
-        checksum = (!checksum + 1) & 0xFF;
-
-        frame.push(checksum);
-        frame.push(PN532_POSTAMBLE);
-        println!("TX: {:02X?}", frame);
-        match self.i2c.write(self.address, &frame) {
-            Ok(_) => {
-                thread::sleep(Duration::from_millis(10));
-                Ok(())
-            }
-            Err(e) => {
-                println!("Write error: {:?}", e);
-                Err(Box::new(e))
-            }
-        }
+        // TFI считается частью полезной нагрузки (Data Packet)
+        let frame = frame::build_frame(PN532_HOSTTOPN532, command);
+        trace!(frame = %logging::bytes(&frame), "TX");
+        self.transport.write_frame(&frame)?;
+        thread::sleep(Duration::from_millis(10));
+        Ok(())
     }
 
-    pub(crate) fn read_ack(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+    pub(crate) fn read_ack(&mut self) -> Result<(), Pn532Error> {
         if !self.wait_ready(50)? {
-            return Err("Timeout waiting for ACK".into());
+            return Err(Pn532Error::Timeout);
         }
-        let mut ack_buf = [0u8; 7];
-        match self.i2c.read(self.address, &mut ack_buf) {
-            Ok(_) => {
-                println!("ACK raw: {:02X?}", ack_buf);
-                // Пропускаем статусный байт
-                let ack = &ack_buf[1..];
+        match self.transport.read_frame(6) {
+            Ok(ack) => {
+                trace!(frame = %logging::bytes(&ack), "ACK raw");
                 // Проверяем ACK: 00 00 FF 00 FF 00
-                if ack[0] == 0x00
-                    && ack[1] == 0x00
-                    && ack[2] == 0xFF
-                    && ack[3] == 0x00
-                    && ack[4] == 0xFF
-                    && ack[5] == 0x00
-                {
-                    println!("PN53x ACKed");
-                    Ok(true)
+                if frame::parse_frame(&ack) == Ok(Frame::Ack) {
+                    Ok(())
                 } else {
-                    println!("Invalid ACK");
-                    Ok(false)
+                    debug!(frame = %logging::bytes(&ack), "invalid ACK");
+                    Err(Pn532Error::NoAck)
                 }
             }
             Err(e) => {
-                println!("ACK read error: {:?}", e);
-                Err(Box::new(e))
+                debug!(error = %e, "ACK read error");
+                Err(e)
             }
         }
     }
+    /// ACK от хоста отменяет текущую команду. Ответ, который чип успел
+    /// выставить до отмены, вычитывается и выбрасывается – иначе его приняли
+    /// бы за ACK или ответ следующей команды.
+    pub(crate) fn cancel_command(&mut self) -> Result<(), Pn532Error> {
+        self.transport.write_frame(&ACK_FRAME)?;
+        if self.wait_ready(CANCEL_DRAIN_MS)? {
+            // +12 – заголовок расширенного фрейма, TFI, код ответа и DCS/POST
+            match self.transport.read_frame(MAX_EXCHANGE_DATA + 12) {
+                Ok(late) => debug!(frame = %logging::bytes(&late), "late response discarded"),
+                Err(e) if e.is_bus_error() => return Err(e),
+                Err(e) => debug!(error = %e, "late response discarded"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Передаёт payload griphd. Пишется во временный файл рядом с целью и
+    /// переименовывается поверх неё, так что griphd видит либо прошлый
+    /// payload, либо новый целиком, но не недописанный.
     pub fn write_to_file(&self, filename: &str, data: &[u8]) -> std::io::Result<()> {
-        // let text_data = data
-        //     .iter()
-        //     .map(|&byte| byte.to_string())
-        //     .collect::<String>();
-        let mut file = OpenOptions::new()
-            .create(true)
+        let path = Path::new(filename);
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+        let tmp = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        // create_new не пойдёт по чужой символической ссылке в /tmp
+        let _ = fs::remove_file(&tmp);
+        let written = OpenOptions::new()
             .write(true)
-            // .append(true)
-            // .truncate(true)
-            .open(filename)?;
+            .create_new(true)
+            .open(&tmp)
+            .and_then(|mut file| {
+                file.write_all(data)?;
+                file.sync_all()
+            })
+            .and_then(|()| fs::rename(&tmp, path));
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        written
+    }
+}
 
-        // writeln!(file, "{}", text_data)?;
-        file.write_all(data)?;
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use crate::pn532reader::mock::{self, SimPn532};
+
+    #[test]
+    fn replaces_payload_file_atomically() {
+        let (pn532, _) = mock::connect(SimPn532::new());
+        let dir = std::env::temp_dir().join(format!("nfc_reader_payload_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("rfid_input.bin");
+        let target_str = target.to_str().unwrap();
+
+        pn532.write_to_file(target_str, &[1, 2, 3, 4]).unwrap();
+        pn532.write_to_file(target_str, &[5, 6]).unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), [5, 6]);
+        // Временный файл не остаётся рядом с payload
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
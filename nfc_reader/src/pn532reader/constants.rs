@@ -9,10 +9,24 @@ pub const PN532_HOSTTOPN532: u8 = 0xD4;
 pub const PN532_PN532TOHOST: u8 = 0xD5;
 
 // Команды PN532
+pub const PN532_COMMAND_DIAGNOSE: u8 = 0x00;
 pub const PN532_COMMAND_GETFIRMWAREVERSION: u8 = 0x02;
 pub const PN532_COMMAND_SAMCONFIGURATION: u8 = 0x14;
+pub const PN532_COMMAND_POWERDOWN: u8 = 0x16;
+pub const PN532_COMMAND_RFCONFIGURATION: u8 = 0x32;
 pub const PN532_COMMAND_INLISTPASSIVETARGET: u8 = 0x4A;
 pub const PN532_COMMAND_INDATAEXCHANGE: u8 = 0x40;
+pub const PN532_COMMAND_INCOMMUNICATETHRU: u8 = 0x42;
+pub const PN532_COMMAND_INRELEASE: u8 = 0x52;
+pub const PN532_COMMAND_INSELECT: u8 = 0x54;
+pub const PN532_COMMAND_INAUTOPOLL: u8 = 0x60;
+pub const PN532_COMMAND_TGINITASTARGET: u8 = 0x8C;
+pub const PN532_COMMAND_TGGETDATA: u8 = 0x86;
+pub const PN532_COMMAND_TGSETDATA: u8 = 0x8E;
+
+// Команды MIFARE, передаваемые через InDataExchange
+pub const MIFARE_CMD_READ: u8 = 0x30;
+pub const MIFARE_CMD_WRITE: u8 = 0xA0;
 
 /*
 | PRE | SC1 | SC2 | LEN | LCS | TFI | DATA... | DCS | POST |
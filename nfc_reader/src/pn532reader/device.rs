@@ -1,183 +1,447 @@
+use crate::logging;
+use crate::metrics::METRICS;
+use crate::pn532reader::card::{classic_sector_of, CardType, ClassicSector, MemoryMap};
 use crate::pn532reader::constants::*;
-use linux_embedded_hal::I2cdev;
+use crate::pn532reader::error::Pn532Error;
+use crate::pn532reader::keys::{KeyStore, KeyType, MifareKey};
+use crate::pn532reader::power::ResetPin;
+use crate::pn532reader::rfconfig::RfConfig;
+use crate::pn532reader::target::parse_target_list;
+pub use crate::pn532reader::target::{Modulation, Target};
+use crate::pn532reader::transport::{I2cTransport, Transport};
 use std::{thread, time::Duration};
+use tracing::{debug, trace, warn};
+
+/// Сколько ждать карту с известным UID: она отвечает с первой же попытки
+/// активации, а промах проверки присутствия не должен стоить полной секунды.
+const SELECT_BY_UID_WAIT_MS: u32 = 100;
+
+/// Трейлер – последний блок сектора: 4 блока в секторах 0-31,
+/// 16 блоков в старших секторах MIFARE Classic 4K (с блока 128).
+pub fn is_sector_trailer(block: u8) -> bool {
+    if block < 128 {
+        block % 4 == 3
+    } else {
+        block % 16 == 15
+    }
+}
+
+/// Чем закончилось чтение сектора MIFARE Classic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectorStatus {
+    Read {
+        key_type: KeyType,
+        key: MifareKey,
+    },
+    /// Ключ подошёл, но часть блоков не прочиталась (access bits).
+    ReadError {
+        key_type: KeyType,
+        key: MifareKey,
+    },
+    AuthFailed,
+}
+
+/// Дамп MIFARE Classic: данные всех блоков подряд и статус каждого сектора.
+#[derive(Debug, Clone, Default)]
+pub struct ClassicDump {
+    pub data: Vec<u8>,
+    pub sectors: Vec<(ClassicSector, SectorStatus)>,
+}
+
+impl ClassicDump {
+    /// Сектора, данные которых в дампе заменены нулями.
+    pub fn unreadable_sectors(&self) -> Vec<u8> {
+        self.sectors
+            .iter()
+            .filter(|(_, status)| !matches!(status, SectorStatus::Read { .. }))
+            .map(|(sector, _)| sector.index)
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.unreadable_sectors().is_empty()
+    }
+}
 
 pub struct PN532 {
-    pub(crate) i2c: I2cdev,
-    pub(crate) address: u8,
+    pub(crate) transport: Box<dyn Transport>,
+    /// RSTPDN для аппаратного сброса, если подключён.
+    pub(crate) reset_pin: Option<Box<dyn ResetPin>>,
+    /// Настройки RFConfiguration, которые заново отправляются после сброса.
+    pub(crate) rf_config: Option<RfConfig>,
 }
 
 impl PN532 {
-    pub fn new(device: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let i2c = I2cdev::new(device)?;
-        Ok(PN532 {
-            i2c,
-            address: PN532_I2C_ADDRESS,
-        })
-    }
-    pub fn get_firmware_version(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
-        self.write_command(&[PN532_COMMAND_GETFIRMWAREVERSION])?;
-        if !self.read_ack()? {
-            return Err("No ACK received".into());
+    /// PN532 на I2C по стандартному адресу 0x24.
+    pub fn new(device: &str) -> Result<Self, Pn532Error> {
+        let transport = I2cTransport::new(device, PN532_I2C_ADDRESS)?;
+        Ok(PN532::with_transport(Box::new(transport)))
+    }
+
+    pub fn with_transport(transport: Box<dyn Transport>) -> Self {
+        PN532 {
+            transport,
+            reset_pin: None,
+            rf_config: None,
         }
-        let response = self.read_response(20)?;
-        if response.len() >= 4 && response[0] == PN532_COMMAND_GETFIRMWAREVERSION + 1 {
+    }
+
+    pub fn wake_up(&mut self) -> Result<(), Pn532Error> {
+        self.transport.wake_up()
+    }
+
+    pub fn uses_irq(&self) -> bool {
+        self.transport.uses_irq()
+    }
+    pub fn get_firmware_version(&mut self) -> Result<u32, Pn532Error> {
+        self.write_command(&[PN532_COMMAND_GETFIRMWAREVERSION])?;
+        self.read_ack()?;
+        let response = self.read_response(PN532_COMMAND_GETFIRMWAREVERSION, 20)?;
+        if response.len() >= 3 {
             let version =
-                ((response[1] as u32) << 16) | ((response[2] as u32) << 8) | (response[3] as u32);
+                ((response[0] as u32) << 16) | ((response[1] as u32) << 8) | (response[2] as u32);
             Ok(version)
         } else {
-            println!("Response: {:02X?}", response);
-            Err("Invalid firmware version response".into())
+            Err(Pn532Error::InvalidResponse("firmware version too short"))
         }
     }
-    pub fn sam_configuration(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn sam_configuration(&mut self) -> Result<(), Pn532Error> {
         self.write_command(&[PN532_COMMAND_SAMCONFIGURATION, 0x01, 0x14, 0x01])?;
 
-        if !self.read_ack()? {
-            return Err("No ACK received".into());
+        self.read_ack()?;
+
+        self.read_response(PN532_COMMAND_SAMCONFIGURATION, 10)?;
+        Ok(())
+    }
+    /// Ищет до `max_targets` карт (не больше двух) с заданной модуляцией.
+    /// Пустой список – карт в поле нет; ошибка – проблемы с PN532 или шиной.
+    pub fn list_passive_targets(
+        &mut self,
+        max_targets: u8,
+        modulation: Modulation,
+    ) -> Result<Vec<Target>, Pn532Error> {
+        let max_targets = max_targets.clamp(1, modulation.max_targets());
+        self.in_list_passive_target(max_targets, modulation, modulation.initiator_data(), 1000)
+    }
+
+    fn in_list_passive_target(
+        &mut self,
+        max_targets: u8,
+        modulation: Modulation,
+        initiator_data: &[u8],
+        wait_ms: u32,
+    ) -> Result<Vec<Target>, Pn532Error> {
+        let mut command = vec![
+            PN532_COMMAND_INLISTPASSIVETARGET,
+            max_targets,
+            modulation.brty(),
+        ];
+        command.extend_from_slice(initiator_data);
+        self.write_command(&command)?;
+        self.read_ack()?;
+        // С IRQ ответ дождётся сам, без лишней паузы
+        if !self.uses_irq() {
+            thread::sleep(Duration::from_millis(50));
         }
+        match self.read_response_within(PN532_COMMAND_INLISTPASSIVETARGET, 255, wait_ms) {
+            Ok(response) => parse_target_list(modulation, &response),
+            // PN532 ищет карту, пока та не появится, – ответа просто нет.
+            // Поиск останавливается ACK-ом, иначе поздний ответ достанется
+            // следующей команде вместо ACK
+            Err(Pn532Error::Timeout) => {
+                self.cancel_command()?;
+                Ok(Vec::new())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Одна карта ISO14443A (Tg = 1). Нет карты – `NoTarget`.
+    pub fn read_passive_target(&mut self) -> Result<Target, Pn532Error> {
+        self.list_passive_targets(1, Modulation::Iso14443A)?
+            .into_iter()
+            .next()
+            .ok_or(Pn532Error::NoTarget)
+    }
+
+    /// Активирует карту ISO14443A с конкретным UID – остальные карты в поле
+    /// не отвечают. Используется для повторной активации после HALT.
+    pub fn select_by_uid(&mut self, uid: &[u8]) -> Result<Target, Pn532Error> {
+        self.in_list_passive_target(1, Modulation::Iso14443A, uid, SELECT_BY_UID_WAIT_MS)?
+            .into_iter()
+            .next()
+            .ok_or(Pn532Error::NoTarget)
+    }
 
-        let response = self.read_response(10)?;
-        if response.len() >= 1 && response[0] == PN532_COMMAND_SAMCONFIGURATION + 1 {
-            Ok(())
+    /// InSelect: снова делает `tg` текущей целью (после InDeselect или
+    /// работы с другой целью).
+    pub fn in_select(&mut self, tg: u8) -> Result<(), Pn532Error> {
+        self.write_command(&[PN532_COMMAND_INSELECT, tg])?;
+        self.read_ack()?;
+        let response = self.read_response(PN532_COMMAND_INSELECT, 10)?;
+        Self::check_status(&response)?;
+        Ok(())
+    }
+
+    /// InRelease: PN532 отправляет карте HLTA/DESELECT и забывает цель.
+    /// `tg = 0` – освободить все цели.
+    pub fn in_release(&mut self, tg: u8) -> Result<(), Pn532Error> {
+        self.write_command(&[PN532_COMMAND_INRELEASE, tg])?;
+        self.read_ack()?;
+        let response = self.read_response(PN532_COMMAND_INRELEASE, 10)?;
+        Self::check_status(&response)?;
+        Ok(())
+    }
+    pub fn read_block(&mut self, block_number: u8) -> Result<Vec<u8>, Pn532Error> {
+        let command = vec![
+            PN532_COMMAND_INDATAEXCHANGE,
+            0x01,
+            MIFARE_CMD_READ,
+            block_number,
+        ];
+
+        self.write_command(&command)?;
+
+        self.read_ack()?;
+
+        let response = self.read_response(PN532_COMMAND_INDATAEXCHANGE, 30)?;
+        let data = Self::check_status(&response)?;
+        if data.len() >= 16 {
+            Ok(data[..16].to_vec())
         } else {
-            Err("SAM configuration failed".into())
+            Err(Pn532Error::InvalidResponse("block data too short"))
         }
     }
-    pub fn read_passive_target(&mut self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        self.write_command(&[PN532_COMMAND_INLISTPASSIVETARGET, 0x01, 0x00])?;
-        if !self.read_ack()? {
-            return Err("No ACK received".into());
+
+    /// MIFARE WRITE (0xA0) – 16 байт в блок; сектор должен быть аутентифицирован.
+    /// Блок 0 и трейлеры секторов (ключи + access bits) без `force` не трогаем:
+    /// неверные access bits навсегда блокируют сектор.
+    pub fn write_block(
+        &mut self,
+        block_number: u8,
+        data: &[u8; 16],
+        force: bool,
+    ) -> Result<(), Pn532Error> {
+        if !force && (block_number == 0 || is_sector_trailer(block_number)) {
+            return Err(Pn532Error::Protected(block_number));
         }
-        thread::sleep(Duration::from_millis(50));
-        match self.read_response(30) {
-            Ok(response) => {
-                if response.len() < 3 || response[0] != PN532_COMMAND_INLISTPASSIVETARGET + 1 {
-                    return Err("Invalid response".into());
-                }
+        let mut command = vec![
+            PN532_COMMAND_INDATAEXCHANGE,
+            0x01,
+            MIFARE_CMD_WRITE,
+            block_number,
+        ];
+        command.extend_from_slice(data);
 
-                let nb_targets = response[1]; // сколько карт найдено.
-                if nb_targets == 0 {
-                    return Err("No card found".into());
-                }
+        self.write_command(&command)?;
 
-                if response.len() < 7 {
-                    return Err("Response too short".into());
-                }
+        self.read_ack()?;
 
-                // _tag_number — номер цели (например, 1)
-                // sens_res — параметры карты (тип, скорость — для ISO14443A)
-                // sel_res — selection response (вторичный ID)
-                // uid_length — сколько байтов занимает UID
+        let response = self.read_response(PN532_COMMAND_INDATAEXCHANGE, 10)?;
+        Self::check_status(&response)?;
+        Ok(())
+    }
 
-                let _tag_number = response[2];
-                let _sens_res = ((response[3] as u16) << 8) | (response[4] as u16);
-                let _sel_res = response[5];
-                let uid_length = response[6] as usize;
+    /// Повторная активация той же карты: после неудачной аутентификации
+    /// MIFARE Classic уходит в HALT и на команды не отвечает. Цель сначала
+    /// освобождается, затем карта заново ищется по UID (WUPA будит HALT-карты).
+    pub fn reselect(&mut self, uid: &[u8]) -> Result<Target, Pn532Error> {
+        match self.in_release(0) {
+            // 0x27 – активной цели уже нет, это не ошибка
+            Ok(()) | Err(Pn532Error::CardStatus(_)) => {}
+            Err(e) => return Err(e),
+        }
+        self.select_by_uid(uid)
+    }
 
-                if response.len() < 7 + uid_length {
-                    return Err("Response too short for UID".into());
+    /// Перебирает ключи сектора из `keys`, пока один не подойдёт. После каждого
+    /// отказа карта активируется заново. `None` – ни один ключ не подошёл.
+    pub fn authenticate_sector(
+        &mut self,
+        sector: &ClassicSector,
+        uid: &[u8],
+        keys: &KeyStore,
+    ) -> Result<Option<(KeyType, MifareKey)>, Pn532Error> {
+        for (key_type, key) in keys.candidates(sector.index) {
+            match self.authenticate_block(sector.first_block, uid, key_type.auth_command(), &key) {
+                Ok(()) => return Ok(Some((key_type, key))),
+                // Неверный ключ или карта не ответила – шина при этом жива
+                Err(Pn532Error::AuthFailed { .. }) | Err(Pn532Error::CardStatus(_)) => {
+                    match self.reselect(uid) {
+                        Ok(_) => {}
+                        Err(e) if e.is_bus_error() => return Err(e),
+                        // Карта ушла из поля – этот сектор уже не прочитать
+                        Err(e) => {
+                            warn!(sector = sector.index, error = %e, "cannot reselect card");
+                            return Ok(None);
+                        }
+                    }
                 }
-
-                Ok(response[7..7 + uid_length].to_vec())
+                Err(e) => return Err(e),
             }
-            Err(_) => Err("No card detected".into()),
         }
+        Ok(None)
     }
-    pub fn read_block(&mut self, block_number: u8) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        let command = vec![PN532_COMMAND_INDATAEXCHANGE, 0x01, 0x30, block_number];
 
-        self.write_command(&command)?;
+    /// Читает все сектора MIFARE Classic, подбирая ключи из `keys`.
+    /// Непрочитанные блоки заполняются нулями, чтобы смещения в дампе сохранялись.
+    pub fn dump_classic(
+        &mut self,
+        sectors: &[ClassicSector],
+        uid: &[u8],
+        keys: &KeyStore,
+    ) -> Result<ClassicDump, Pn532Error> {
+        let mut dump = ClassicDump::default();
 
-        if !self.read_ack()? {
-            return Err("No ACK received".into());
+        for sector in sectors {
+            let start = dump.data.len();
+            let status = match self.authenticate_sector(sector, uid, keys)? {
+                Some((key_type, key)) => {
+                    debug!(sector = sector.index, ?key_type, "sector authenticated");
+                    let mut failed = false;
+                    for block in sector.blocks() {
+                        match self.read_block(block) {
+                            Ok(data) => {
+                                trace!(block, data = %logging::bytes(&data), "block read");
+                                dump.data.extend_from_slice(&data);
+                            }
+                            Err(e @ Pn532Error::CardStatus(_)) => {
+                                warn!(block, error = %e, "cannot read block");
+                                dump.data.extend_from_slice(&[0u8; 16]); // Записываем пустой блок, чтобы размер оставался
+                                failed = true;
+                                // NAK на READ тоже переводит карту в HALT – будим и
+                                // аутентифицируемся тем же ключом, чтобы дочитать сектор
+                                let resumed = self.reselect(uid).and_then(|_| {
+                                    self.authenticate_block(
+                                        sector.first_block,
+                                        uid,
+                                        key_type.auth_command(),
+                                        &key,
+                                    )
+                                });
+                                match resumed {
+                                    Ok(()) => {}
+                                    Err(e) if e.is_bus_error() => return Err(e),
+                                    // Остаток сектора не дочитать, но дамп продолжается
+                                    Err(e) => {
+                                        warn!(sector = sector.index, error = %e, "cannot resume sector");
+                                        break;
+                                    }
+                                }
+                            }
+                            Err(e) => return Err(e),
+                        }
+                    }
+                    dump.data
+                        .resize(start + sector.block_count as usize * 16, 0);
+                    if failed {
+                        SectorStatus::ReadError { key_type, key }
+                    } else {
+                        SectorStatus::Read { key_type, key }
+                    }
+                }
+                None => {
+                    warn!(sector = sector.index, "no key for sector");
+                    METRICS
+                        .auth_failures
+                        .with_label_values(&[&sector.index.to_string()])
+                        .inc();
+                    dump.data
+                        .extend(vec![0u8; sector.block_count as usize * 16]);
+                    SectorStatus::AuthFailed
+                }
+            };
+            dump.sectors.push((*sector, status));
         }
 
-        let response = self.read_response(30)?;
+        Ok(dump)
+    }
 
-        if response.len() >= 18
-            && response[0] == PN532_COMMAND_INDATAEXCHANGE + 1
-            && response[1] == 0x00
-        {
-            Ok(response[2..18].to_vec())
-        } else {
-            Err("Failed to read block".into())
-        }
-    }
-
-    // pub fn dump_available_data(
-    //     &mut self,
-    //     uid: &[u8],
-    // ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    //     let keys = [[0xFF; 6], [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5], [0x00; 6]];
-    //     let mut result = Vec::new();
-
-    //     for block in 0..64 {
-    //         if block % 4 == 3 {
-    //             continue; // skip trailer
-    //         }
-
-    //         let mut authed = false;
-    //         for key in keys {
-    //             if self.authenticate_block(block, uid, 0x60, &key)? {
-    //                 authed = true;
-    //                 break;
-    //             }
-    //             if self.authenticate_block(block, uid, 0x61, &key)? {
-    //                 authed = true;
-    //                 break;
-    //             }
-    //         }
-
-    //         if !authed {
-    //             result.extend_from_slice(&[b'?'; 16]); // или b'-'
-    //             continue;
-    //         }
-
-    //         let block_data = self.read_block(block)?;
-    //         result.extend_from_slice(&block_data);
-    //     }
-
-    //     Ok(result)
-    // }
-
-    /// Считывает все data-блоки (без трейлеров) и собирает строку из них
-    pub fn read_full_data(
+    /// Пишет `data` в блоки данных MIFARE Classic подряд (с сектора 1, без
+    /// трейлеров) и читает обратно. Не поместившиеся данные отвергаются до
+    /// первой записи. Трейлеры и блок 0 этот путь не трогает никогда.
+    pub fn write_classic_data(
         &mut self,
+        card: &CardType,
         uid: &[u8],
-        auth_key: u8,
-        key: &[u8; 6],
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        let mut all_data = Vec::new();
-
-        for sector in 0..16 {
-            let block_start = sector * 4;
-
-            // Аутентификация производится только один раз на сектор – по первому блоку (сюда входит весь сектор)
-            let auth_result = self.authenticate_block(block_start, uid, auth_key, key)?;
-
-            if auth_result {
-                println!("Auth success on sector {}", sector);
-                for i in 0..4 {
-                    let block = block_start + i;
-                    match self.read_block(block) {
-                        Ok(data) => {
-                            println!("  Block {:2}: {:?}", block, data);
-                            all_data.extend_from_slice(&data);
-                        }
-                        Err(e) => {
-                            eprintln!("  ❌ Failed to read block {}: {}", block, e);
-                            all_data.extend_from_slice(&[0u8; 16]); // Записываем пустой блок, чтобы размер оставался
-                        }
+        keys: &KeyStore,
+        data: &[u8],
+    ) -> Result<(), Pn532Error> {
+        let map = card.memory_map();
+        let MemoryMap::Classic(sectors) = &map else {
+            return Err(Pn532Error::Unsupported("not a MIFARE Classic card"));
+        };
+        let available = map.classic_data_blocks();
+        let chunks: Vec<[u8; 16]> = data
+            .chunks(16)
+            .map(|chunk| {
+                let mut block = [0u8; 16];
+                block[..chunk.len()].copy_from_slice(chunk);
+                block
+            })
+            .collect();
+        if chunks.len() > available.len() {
+            return Err(Pn532Error::PayloadTooLarge {
+                len: data.len(),
+                capacity: available.len() * 16,
+            });
+        }
+        let blocks = &available[..chunks.len()];
+
+        for verify in [false, true] {
+            // Аутентификация нужна при каждом переходе в новый сектор
+            let mut sector = None;
+            for (&block, chunk) in blocks.iter().zip(&chunks) {
+                let current = &sectors[classic_sector_of(block) as usize];
+                if sector != Some(current.index) {
+                    if self.authenticate_sector(current, uid, keys)?.is_none() {
+                        return Err(Pn532Error::AuthFailed { block });
                     }
+                    sector = Some(current.index);
+                }
+                if !verify {
+                    self.write_block(block, chunk, false)?;
+                    debug!(block, "block written");
+                } else if self.read_block(block)? != chunk {
+                    return Err(Pn532Error::VerificationFailed(block));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Считывает всю память карты по её карте памяти: все сектора Classic
+    /// (включая трейлеры) или все страницы Type 2. Если хоть один сектор не
+    /// прочитан – `UnreadableSectors`; частичный дамп отдаёт `dump_classic`.
+    pub fn read_full_data(
+        &mut self,
+        card: &CardType,
+        uid: &[u8],
+        keys: &KeyStore,
+    ) -> Result<Vec<u8>, Pn532Error> {
+        match card.memory_map() {
+            MemoryMap::Classic(sectors) => {
+                let dump = self.dump_classic(&sectors, uid, keys)?;
+                match dump.unreadable_sectors() {
+                    unreadable if unreadable.is_empty() => Ok(dump.data),
+                    unreadable => Err(Pn532Error::UnreadableSectors(unreadable)),
                 }
-            } else {
-                println!("❌ Auth failed on sector {}", sector);
-                all_data.extend_from_slice(&[0u8; 64]); // 4 блока по 16 байт
             }
+            MemoryMap::Pages { total_pages, .. } => self.read_all_pages(total_pages),
+            MemoryMap::Application => Err(Pn532Error::Unsupported(
+                "card memory is only reachable via APDU",
+            )),
         }
+    }
 
-        Ok(all_data)
+    /// READ отдаёт по 4 страницы; хвост после последней страницы отрезаем.
+    fn read_all_pages(&mut self, total_pages: u8) -> Result<Vec<u8>, Pn532Error> {
+        let mut data = Vec::with_capacity(total_pages as usize * 4 + 12);
+        for page in (0..total_pages).step_by(4) {
+            data.extend_from_slice(&self.read_block(page)?);
+        }
+        data.truncate(total_pages as usize * 4);
+        Ok(data)
     }
 
     pub fn authenticate_block(
@@ -186,27 +450,339 @@ impl PN532 {
         uid: &[u8],
         key_type: u8,  // 0x60 = KEY_A, 0x61 = KEY_B
         key: &[u8; 6], // 6 байт ключа
-    ) -> Result<bool, Box<dyn std::error::Error>> {
+    ) -> Result<(), Pn532Error> {
         let mut command = vec![PN532_COMMAND_INDATAEXCHANGE, 0x01, key_type, block_number];
         command.extend_from_slice(key);
         command.extend_from_slice(uid);
 
         self.write_command(&command)?;
 
-        if !self.read_ack()? {
-            return Err("No ACK received after auth".into());
+        self.read_ack()?;
+
+        let response = self.read_response(PN532_COMMAND_INDATAEXCHANGE, 10)?;
+        match Self::check_status(&response) {
+            Ok(_) => {
+                trace!(block = block_number, "auth success");
+                Ok(())
+            }
+            Err(Pn532Error::CardStatus(status)) if status & 0x3F == 0x14 => {
+                trace!(block = block_number, "auth failed");
+                Err(Pn532Error::AuthFailed {
+                    block: block_number,
+                })
+            }
+            Err(e) => Err(e),
         }
+    }
+}
 
-        let response = self.read_response(10)?;
-        if response.len() >= 2
-            && response[0] == PN532_COMMAND_INDATAEXCHANGE + 1
-            && response[1] == 0x00
-        {
-            println!("Auth success for block {}", block_number);
-            Ok(true)
-        } else {
-            println!("Auth failed: {:?}", response);
-            Ok(false)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn532reader::frame::build_frame;
+    use crate::pn532reader::mock::{self, ClassicTag, SimPn532, VirtualTag};
+
+    const UID: [u8; 4] = [0xDE, 0xAD, 0xBE, 0xEF];
+
+    fn classic_reader() -> (PN532, std::rc::Rc<std::cell::RefCell<SimPn532>>) {
+        mock::connect(SimPn532::with_tag(VirtualTag::Classic(
+            ClassicTag::blank_1k(UID),
+        )))
+    }
+
+    #[test]
+    fn firmware_version_and_sam() {
+        let (mut pn532, _) = mock::connect(SimPn532::new());
+        assert_eq!(pn532.get_firmware_version().unwrap(), 0x320106);
+        pn532.sam_configuration().unwrap();
+    }
+
+    #[test]
+    fn checksum_error_is_retransmitted_after_nack() {
+        let (mut pn532, chip) = mock::connect(SimPn532::new());
+        chip.borrow_mut().corrupt_next_response = true;
+        assert_eq!(pn532.get_firmware_version().unwrap(), 0x320106);
+        // Повтор запрошен NACK-ом, новой команды не было
+        assert_eq!(chip.borrow().received.len(), 1);
+    }
+
+    #[test]
+    fn passive_target_reports_uid_and_sak() {
+        let (mut pn532, chip) = classic_reader();
+        let target = pn532.read_passive_target().unwrap();
+        assert_eq!(target.uid, UID);
+        assert_eq!(target.sens_res, 0x0004);
+        assert_eq!(target.sel_res, 0x08);
+
+        chip.borrow_mut().tag = None;
+        assert!(matches!(
+            pn532.read_passive_target(),
+            Err(Pn532Error::NoTarget)
+        ));
+    }
+
+    #[test]
+    fn endless_search_of_empty_field_is_cancelled() {
+        let (mut pn532, chip) = classic_reader();
+        pn532.set_max_retries(0xFF, 0x01, 0xFF).unwrap();
+        let tag = chip.borrow_mut().tag.take();
+
+        // Карта нашлась уже после таймаута – её ответ вычитывается отменой,
+        // а не достаётся следующей команде вместо ACK
+        chip.borrow_mut().response_in_flight = Some(build_frame(
+            PN532_PN532TOHOST,
+            &[PN532_COMMAND_INLISTPASSIVETARGET + 1, 0x00],
+        ));
+        assert!(matches!(
+            pn532.read_passive_target(),
+            Err(Pn532Error::NoTarget)
+        ));
+        assert!(!pn532.wait_ready(0).unwrap());
+        assert_eq!(pn532.get_firmware_version().unwrap(), 0x320106);
+
+        assert!(matches!(
+            pn532.select_by_uid(&UID),
+            Err(Pn532Error::NoTarget)
+        ));
+        chip.borrow_mut().tag = tag;
+        assert_eq!(pn532.select_by_uid(&UID).unwrap().uid, UID);
+    }
+
+    #[test]
+    fn lists_two_targets_and_other_modulations() {
+        let (mut pn532, chip) = classic_reader();
+        chip.borrow_mut().second_tag = Some(VirtualTag::Ntag(mock::NtagTag::blank(
+            crate::pn532reader::ntag::NtagModel::Ntag213,
+            [0x04, 1, 2, 3, 4, 5, 6],
+        )));
+
+        let targets = pn532
+            .list_passive_targets(2, Modulation::Iso14443A)
+            .unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!((targets[0].tg, targets[0].sel_res), (1, 0x08));
+        assert_eq!(targets[1].tg, 2);
+        assert_eq!(targets[1].sens_res, 0x0044);
+        assert_eq!(targets[1].uid.len(), 7);
+        assert!(targets.iter().all(|t| t.ats.is_none()));
+
+        // MaxTg = 1 – только первая
+        assert_eq!(pn532.read_passive_target().unwrap().uid, UID);
+        assert!(pn532
+            .list_passive_targets(2, Modulation::Felica212)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            chip.borrow().received.last().unwrap(),
+            &[0x4A, 0x02, 0x01, 0x00, 0xFF, 0xFF, 0x01, 0x00]
+        );
+    }
+
+    #[test]
+    fn authenticate_checks_key() {
+        let (mut pn532, _) = classic_reader();
+        pn532.read_passive_target().unwrap();
+        pn532.authenticate_block(4, &UID, 0x60, &[0xFF; 6]).unwrap();
+        assert!(matches!(
+            pn532.authenticate_block(8, &UID, 0x60, &[0x00; 6]),
+            Err(Pn532Error::AuthFailed { block: 8 })
+        ));
+        // После неудачной аутентификации карта в HALT и не отвечает
+        assert!(matches!(
+            pn532.read_block(8),
+            Err(Pn532Error::CardStatus(0x01))
+        ));
+    }
+
+    #[test]
+    fn write_classic_data_skips_trailers_and_verifies() {
+        let (mut pn532, chip) = classic_reader();
+        if let Some(VirtualTag::Classic(tag)) = chip.borrow_mut().tag.as_mut() {
+            tag.set_keys(2, [0x11; 6], [0x22; 6]);
+        }
+        let mut keys = KeyStore::default();
+        keys.add_sector_key(2, KeyType::B, [0x22; 6]);
+        let card = CardType::MifareClassic1K;
+        pn532.read_passive_target().unwrap();
+
+        // Три блока сектора 1 и начало сектора 2
+        let data: Vec<u8> = (0..60).collect();
+        pn532.write_classic_data(&card, &UID, &keys, &data).unwrap();
+        if let Some(VirtualTag::Classic(tag)) = chip.borrow().tag.as_ref() {
+            assert_eq!(&tag.memory[64..112], &data[..48]);
+            assert_eq!(&tag.memory[128..140], &data[48..]);
+            assert_eq!(&tag.memory[140..144], &[0; 4]);
+        }
+
+        // 45 блоков данных в секторах 1-15
+        assert!(matches!(
+            pn532.write_classic_data(&card, &UID, &keys, &[0; 45 * 16 + 1]),
+            Err(Pn532Error::PayloadTooLarge { capacity: 720, .. })
+        ));
+        assert!(matches!(
+            pn532.write_classic_data(&card, &UID, &KeyStore::default(), &data),
+            Err(Pn532Error::AuthFailed { block: 8 })
+        ));
+    }
+
+    #[test]
+    fn read_full_data_returns_all_sectors() {
+        let (mut pn532, chip) = classic_reader();
+        if let Some(VirtualTag::Classic(tag)) = chip.borrow_mut().tag.as_mut() {
+            tag.memory[4 * 16..4 * 16 + 5].copy_from_slice(b"hello");
+        }
+        let target = pn532.read_passive_target().unwrap();
+        let data = pn532
+            .read_full_data(
+                &CardType::MifareClassic1K,
+                &target.uid,
+                &KeyStore::default(),
+            )
+            .unwrap();
+        assert_eq!(data.len(), 1024);
+        assert_eq!(&data[..4], &UID);
+        assert_eq!(&data[64..69], b"hello");
+    }
+
+    #[test]
+    fn dump_tries_keys_and_reselects_after_failure() {
+        let (mut pn532, chip) = classic_reader();
+        if let Some(VirtualTag::Classic(tag)) = chip.borrow_mut().tag.as_mut() {
+            tag.set_keys(2, [0x11; 6], [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5]);
+            tag.set_keys(3, [0x22; 6], [0x33; 6]);
+            tag.set_keys(4, [0x44; 6], [0x55; 6]);
+            tag.memory[16 * 16..16 * 16 + 4].copy_from_slice(b"sec4");
+        }
+        let mut keys = KeyStore::default();
+        keys.add_sector_key(4, KeyType::B, [0x55; 6]);
+
+        pn532.read_passive_target().unwrap();
+        let MemoryMap::Classic(sectors) = CardType::MifareClassic1K.memory_map() else {
+            unreachable!()
+        };
+        let dump = pn532.dump_classic(&sectors, &UID, &keys).unwrap();
+        assert_eq!(dump.data.len(), 1024);
+        assert_eq!(
+            dump.sectors[2].1,
+            SectorStatus::Read {
+                key_type: KeyType::B,
+                key: [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5]
+            }
+        );
+        assert_eq!(
+            dump.sectors[4].1,
+            SectorStatus::Read {
+                key_type: KeyType::B,
+                key: [0x55; 6]
+            }
+        );
+        assert_eq!(&dump.data[256..260], b"sec4");
+        // Сектор 3 не прочитан, но следующие за ним – да
+        assert_eq!(dump.unreadable_sectors(), [3]);
+    }
+
+    #[test]
+    fn dump_continues_when_card_cannot_be_reselected() {
+        let (mut pn532, chip) = classic_reader();
+        if let Some(VirtualTag::Classic(tag)) = chip.borrow_mut().tag.as_mut() {
+            tag.unreadable_blocks.push(9);
+        }
+        pn532.read_passive_target().unwrap();
+        let MemoryMap::Classic(sectors) = CardType::MifareClassic1K.memory_map() else {
+            unreachable!()
+        };
+        let keys = KeyStore::default();
+
+        // Блок 9 не читается, но карта остаётся в поле – сектор дочитывается
+        let dump = pn532.dump_classic(&sectors, &UID, &keys).unwrap();
+        assert!(matches!(dump.sectors[2].1, SectorStatus::ReadError { .. }));
+        assert_eq!(dump.unreadable_sectors(), [2]);
+        assert_eq!(dump.sectors[3].1, dump.sectors[1].1);
+
+        // После NAK карту унесли: сектор 2 недочитан, остальные не аутентифицируются
+        if let Some(VirtualTag::Classic(tag)) = chip.borrow_mut().tag.as_mut() {
+            tag.leaves_field_on_nak = true;
+        }
+        pn532.reselect(&UID).unwrap();
+        let dump = pn532.dump_classic(&sectors, &UID, &keys).unwrap();
+        assert_eq!(dump.data.len(), 1024);
+        assert_eq!(
+            dump.sectors[1].1,
+            SectorStatus::Read {
+                key_type: KeyType::A,
+                key: [0xFF; 6]
+            }
+        );
+        assert!(matches!(dump.sectors[2].1, SectorStatus::ReadError { .. }));
+        assert_eq!(dump.sectors[3].1, SectorStatus::AuthFailed);
+        assert_eq!(dump.unreadable_sectors(), (2..16).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn read_full_data_reports_unreadable_sectors() {
+        let (mut pn532, chip) = classic_reader();
+        if let Some(VirtualTag::Classic(tag)) = chip.borrow_mut().tag.as_mut() {
+            tag.set_keys(7, [0x77; 6], [0x77; 6]);
         }
+        let target = pn532.read_passive_target().unwrap();
+        let result = pn532.read_full_data(
+            &CardType::MifareClassic1K,
+            &target.uid,
+            &KeyStore::default(),
+        );
+        assert!(matches!(result, Err(Pn532Error::UnreadableSectors(ref s)) if s == &[7]));
+    }
+
+    #[test]
+    fn release_halts_and_select_by_uid_wakes() {
+        let (mut pn532, chip) = classic_reader();
+        chip.borrow_mut().second_tag =
+            Some(VirtualTag::Classic(ClassicTag::blank_1k([1, 2, 3, 4])));
+        pn532.read_passive_target().unwrap();
+        pn532.in_release(0).unwrap();
+        assert!(matches!(
+            pn532.read_block(4),
+            Err(Pn532Error::CardStatus(_))
+        ));
+
+        // Выбирается именно карта с нужным UID, даже если она вторая в поле
+        let target = pn532.select_by_uid(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(target.uid, [1, 2, 3, 4]);
+        assert!(matches!(
+            pn532.select_by_uid(&[9, 9, 9, 9]),
+            Err(Pn532Error::NoTarget)
+        ));
+
+        pn532.reselect(&UID).unwrap();
+        pn532.authenticate_block(4, &UID, 0x60, &[0xFF; 6]).unwrap();
+        pn532.in_select(1).unwrap();
+    }
+
+    #[test]
+    fn write_block_round_trip() {
+        let (mut pn532, chip) = classic_reader();
+        pn532.read_passive_target().unwrap();
+        pn532.authenticate_block(5, &UID, 0x60, &[0xFF; 6]).unwrap();
+        pn532.write_block(5, &[0x42; 16], false).unwrap();
+        assert_eq!(pn532.read_block(5).unwrap(), vec![0x42; 16]);
+        let chip = chip.borrow();
+        match chip.tag.as_ref() {
+            Some(VirtualTag::Classic(tag)) => assert_eq!(tag.memory[80..96], [0x42; 16]),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn write_block_refuses_trailer_without_force() {
+        let (mut pn532, chip) = classic_reader();
+        pn532.read_passive_target().unwrap();
+        let sent = chip.borrow().received.len();
+        assert!(matches!(
+            pn532.write_block(7, &[0x00; 16], false),
+            Err(Pn532Error::Protected(7))
+        ));
+        assert!(pn532.write_block(0, &[0x00; 16], false).is_err());
+        // До чипа такие команды не доходят
+        assert_eq!(chip.borrow().received.len(), sent);
     }
 }
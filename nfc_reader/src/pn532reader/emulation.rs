@@ -0,0 +1,318 @@
+use crate::logging;
+use crate::pn532reader::apdu::{
+    Apdu, CC_FILE_ID, MAX_EXCHANGE_DATA, MI_FLAG, NDEF_AID, NDEF_FILE_ID,
+};
+use crate::pn532reader::constants::*;
+use crate::pn532reader::device::PN532;
+use crate::pn532reader::error::Pn532Error;
+use tracing::{debug, info};
+
+/// MLe эмулируемой метки: R-APDU с SW должен влезать в одну TgSetData.
+const EMULATION_MAX_READ: u16 = 0xFF;
+/// Сколько ждать телефон за одну TgInitAsTarget.
+const TG_INIT_TIMEOUT_MS: u32 = 1000;
+/// Код ошибки PN532: инициатор ушёл (RF-поле пропало или DESELECT).
+const STATUS_RELEASED: u8 = 0x29;
+
+/// NFC Forum Type 4 метка с одним NDEF-файлом: отвечает на SELECT приложения
+/// NDEF, SELECT файлов CC/NDEF, READ BINARY и, если разрешено, UPDATE BINARY.
+/// Используется и PN532 в режиме карты, и виртуальной меткой симулятора.
+#[derive(Debug, Clone)]
+pub struct Type4Emulator {
+    /// NLEN (2 байта) + NDEF-сообщение, добитые нулями до размера файла.
+    pub ndef_file: Vec<u8>,
+    /// MLe в CC – сколько байт отдаётся за один READ BINARY.
+    pub max_read: u16,
+    pub writable: bool,
+    app_selected: bool,
+    selected: Option<u16>,
+    /// До какого байта NDEF-файла инициатор дочитал.
+    read_up_to: usize,
+}
+
+impl Type4Emulator {
+    /// Файл ровно под сообщение, только для чтения.
+    pub fn new(message: &[u8]) -> Self {
+        Self::with_file_size(message, message.len() + 2)
+    }
+
+    pub fn with_file_size(message: &[u8], file_size: usize) -> Self {
+        let mut ndef_file = vec![0u8; file_size.max(message.len() + 2)];
+        ndef_file[..2].copy_from_slice(&(message.len() as u16).to_be_bytes());
+        ndef_file[2..2 + message.len()].copy_from_slice(message);
+        Type4Emulator {
+            ndef_file,
+            max_read: EMULATION_MAX_READ,
+            writable: false,
+            app_selected: false,
+            selected: None,
+            read_up_to: 0,
+        }
+    }
+
+    /// Сбрасывает выбор приложения – новая активация метки.
+    pub fn reset(&mut self) {
+        self.app_selected = false;
+        self.selected = None;
+    }
+
+    /// Прочитал ли инициатор NLEN и сообщение целиком.
+    pub fn message_read(&self) -> bool {
+        let nlen = u16::from_be_bytes([self.ndef_file[0], self.ndef_file[1]]) as usize;
+        self.read_up_to >= nlen + 2
+    }
+
+    fn cc(&self) -> Vec<u8> {
+        let mut cc = vec![0x00, 0x0F, 0x20];
+        cc.extend_from_slice(&self.max_read.to_be_bytes());
+        // MLc, затем NDEF File Control TLV
+        cc.extend_from_slice(&[0x00, 0xFF, 0x04, 0x06]);
+        cc.extend_from_slice(&NDEF_FILE_ID.to_be_bytes());
+        cc.extend_from_slice(&(self.ndef_file.len() as u16).to_be_bytes());
+        cc.push(0x00);
+        cc.push(if self.writable { 0x00 } else { 0xFF });
+        cc
+    }
+
+    /// Ответ (данные + SW) на C-APDU инициатора.
+    pub fn respond(&mut self, command: &[u8]) -> Vec<u8> {
+        let sw = |sw: u16| sw.to_be_bytes().to_vec();
+        let Some(apdu) = Apdu::parse(command) else {
+            return sw(0x6700);
+        };
+        let offset = u16::from_be_bytes([apdu.p1, apdu.p2]) as usize;
+        match (apdu.ins, apdu.p1) {
+            (0xA4, 0x04) => {
+                self.app_selected = apdu.data == NDEF_AID;
+                self.selected = None;
+                sw(if self.app_selected { 0x9000 } else { 0x6A82 })
+            }
+            (0xA4, 0x00) if self.app_selected && apdu.data.len() == 2 => {
+                let id = u16::from_be_bytes([apdu.data[0], apdu.data[1]]);
+                if id == CC_FILE_ID || id == NDEF_FILE_ID {
+                    self.selected = Some(id);
+                    sw(0x9000)
+                } else {
+                    sw(0x6A82)
+                }
+            }
+            (0xB0, _) => {
+                let file = match self.selected {
+                    Some(CC_FILE_ID) => self.cc(),
+                    Some(NDEF_FILE_ID) => self.ndef_file.clone(),
+                    _ => return sw(0x6986),
+                };
+                if offset > file.len() {
+                    return sw(0x6B00);
+                }
+                // Больше MLe за раз не отдаём, даже если инициатор просит
+                let le = apdu.le.unwrap_or(0).min(self.max_read as usize);
+                let end = (offset + le).min(file.len());
+                if self.selected == Some(NDEF_FILE_ID) {
+                    self.read_up_to = self.read_up_to.max(end);
+                }
+                let mut out = file[offset..end].to_vec();
+                out.extend(sw(if end - offset < le { 0x6282 } else { 0x9000 }));
+                out
+            }
+            (0xD6, _) if self.selected == Some(NDEF_FILE_ID) && self.writable => {
+                match self.ndef_file.get_mut(offset..offset + apdu.data.len()) {
+                    Some(target) => {
+                        target.copy_from_slice(&apdu.data);
+                        sw(0x9000)
+                    }
+                    None => sw(0x6700),
+                }
+            }
+            (0xD6, _) => sw(0x6982),
+            _ => sw(0x6D00),
+        }
+    }
+}
+
+impl PN532 {
+    /// TgInitAsTarget: PN532 притворяется картой ISO14443-4 (PICC) с UID
+    /// `08 uid[0] uid[1] uid[2]` и ждёт инициатора. Возвращает первую команду
+    /// инициатора; `Timeout` – за `timeout_ms` никто не пришёл.
+    pub fn tg_init_as_target(
+        &mut self,
+        uid: [u8; 3],
+        timeout_ms: u32,
+    ) -> Result<Vec<u8>, Pn532Error> {
+        // Mode: PassiveOnly | PICCOnly
+        let mut command = vec![PN532_COMMAND_TGINITASTARGET, 0x05];
+        // MifareParams: SENS_RES, NFCID1t, SEL_RES (ISO14443-4)
+        command.extend_from_slice(&[0x04, 0x00]);
+        command.extend_from_slice(&uid);
+        command.push(0x20);
+        // FeliCaParams и NFCID3t не нужны, Gt и Tk пустые
+        command.extend_from_slice(&[0u8; 18 + 10]);
+        command.extend_from_slice(&[0x00, 0x00]);
+        self.write_command(&command)?;
+        self.read_ack()?;
+        let response = match self.read_response_within(
+            PN532_COMMAND_TGINITASTARGET,
+            MAX_EXCHANGE_DATA + 1,
+            timeout_ms,
+        ) {
+            Ok(response) => response,
+            Err(Pn532Error::Timeout) => {
+                // ACK от хоста отменяет ожидание инициатора
+                self.cancel_command()?;
+                return Err(Pn532Error::Timeout);
+            }
+            Err(e) => return Err(e),
+        };
+        let (mode, initiator_command) = response
+            .split_first()
+            .ok_or(Pn532Error::InvalidResponse("empty TgInitAsTarget response"))?;
+        info!(mode = format_args!("{:02X}", mode), "activated as target");
+        Ok(initiator_command.to_vec())
+    }
+
+    /// TgGetData: следующая команда инициатора, части с MI склеиваются.
+    pub fn tg_get_data(&mut self) -> Result<Vec<u8>, Pn532Error> {
+        let mut data = Vec::new();
+        loop {
+            self.write_command(&[PN532_COMMAND_TGGETDATA])?;
+            self.read_ack()?;
+            let response = self.read_response(PN532_COMMAND_TGGETDATA, MAX_EXCHANGE_DATA + 1)?;
+            let (&status, chunk) = response
+                .split_first()
+                .ok_or(Pn532Error::InvalidResponse("empty response"))?;
+            if status & 0x3F != 0 {
+                return Err(Pn532Error::CardStatus(status));
+            }
+            data.extend_from_slice(chunk);
+            if status & MI_FLAG == 0 {
+                return Ok(data);
+            }
+        }
+    }
+
+    /// TgSetData: ответ инициатору, не длиннее одного кадра.
+    pub fn tg_set_data(&mut self, data: &[u8]) -> Result<(), Pn532Error> {
+        if data.len() > MAX_EXCHANGE_DATA {
+            return Err(Pn532Error::Unsupported("TgSetData longer than 262 bytes"));
+        }
+        let mut command = vec![PN532_COMMAND_TGSETDATA];
+        command.extend_from_slice(data);
+        self.write_command(&command)?;
+        self.read_ack()?;
+        let response = self.read_response(PN532_COMMAND_TGSETDATA, 10)?;
+        Self::check_status(&response)?;
+        Ok(())
+    }
+
+    /// Одна сессия эмуляции Type 4 метки: ждёт телефон, отвечает на его APDU,
+    /// пока тот не уйдёт. `Ok(true)` – NDEF-сообщение прочитано целиком.
+    pub fn emulate_type4(
+        &mut self,
+        emulator: &mut Type4Emulator,
+        uid: [u8; 3],
+    ) -> Result<bool, Pn532Error> {
+        emulator.reset();
+        let mut command = self.tg_init_as_target(uid, TG_INIT_TIMEOUT_MS)?;
+        loop {
+            debug!(apdu = %logging::bytes(&command), "initiator C-APDU");
+            let response = emulator.respond(&command);
+            match self.tg_set_data(&response) {
+                Ok(()) => {}
+                Err(Pn532Error::CardStatus(status)) if status & 0x3F == STATUS_RELEASED => break,
+                Err(e) => return Err(e),
+            }
+            command = match self.tg_get_data() {
+                Ok(command) => command,
+                Err(Pn532Error::CardStatus(status)) if status & 0x3F == STATUS_RELEASED => break,
+                Err(e) => return Err(e),
+            };
+        }
+        info!("initiator released the target");
+        Ok(emulator.message_read())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn532reader::mock::{self, SimInitiator, SimPn532};
+
+    /// Что делает телефон, читая NDEF с Type 4 метки.
+    fn phone_reading(message_len: usize) -> SimInitiator {
+        let mut commands = vec![
+            Apdu::select_aid(&NDEF_AID).to_bytes(),
+            Apdu::select_file(CC_FILE_ID).to_bytes(),
+            Apdu::read_binary(0, 15).to_bytes(),
+            Apdu::select_file(NDEF_FILE_ID).to_bytes(),
+            Apdu::read_binary(0, 2).to_bytes(),
+        ];
+        // Сообщение кусками по MLe из CC
+        for offset in (0..message_len).step_by(EMULATION_MAX_READ as usize) {
+            let len = (message_len - offset).min(EMULATION_MAX_READ as usize);
+            commands.push(Apdu::read_binary(offset as u16 + 2, len).to_bytes());
+        }
+        SimInitiator::new(commands)
+    }
+
+    #[test]
+    fn emulator_serves_ndef_file() {
+        let mut emulator = Type4Emulator::new(b"\xD1\x01\x04T\x02enhi");
+        assert_eq!(
+            emulator.respond(&Apdu::read_binary(0, 2).to_bytes()),
+            [0x69, 0x86]
+        );
+        assert_eq!(
+            emulator.respond(&Apdu::select_aid(&NDEF_AID).to_bytes()),
+            [0x90, 0x00]
+        );
+        assert_eq!(
+            emulator.respond(&Apdu::select_file(CC_FILE_ID).to_bytes()),
+            [0x90, 0x00]
+        );
+        let cc = emulator.respond(&Apdu::read_binary(0, 15).to_bytes());
+        assert_eq!(cc[7..15], [0x04, 0x06, 0xE1, 0x04, 0x00, 0x0B, 0x00, 0xFF]);
+        emulator.respond(&Apdu::select_file(NDEF_FILE_ID).to_bytes());
+        assert_eq!(
+            emulator.respond(&Apdu::update_binary(0, &[0, 0]).to_bytes()),
+            [0x69, 0x82]
+        );
+        assert!(!emulator.message_read());
+        let file = emulator.respond(&Apdu::read_binary(0, 20).to_bytes());
+        assert_eq!(file[..11], emulator.ndef_file[..]);
+        assert_eq!(file[11..], [0x62, 0x82]);
+        assert!(emulator.message_read());
+    }
+
+    #[test]
+    fn phone_reads_emulated_tag() {
+        let message = vec![0xD1; 300];
+        let mut chip = SimPn532::new();
+        chip.initiator = Some(phone_reading(message.len()));
+        let (mut pn532, chip) = mock::connect(chip);
+        let mut emulator = Type4Emulator::new(&message);
+
+        assert!(pn532.emulate_type4(&mut emulator, [1, 2, 3]).unwrap());
+        let chip = chip.borrow();
+        let responses = &chip.initiator.as_ref().unwrap().responses;
+        assert_eq!(responses.len(), 7);
+        assert_eq!(responses[4], [0x01, 0x2C, 0x90, 0x00]);
+        assert_eq!(responses[5].len(), 255 + 2);
+        assert_eq!(responses[6].len(), 45 + 2);
+    }
+
+    #[test]
+    fn waits_for_initiator_and_handles_early_release() {
+        let (mut pn532, chip) = mock::connect(SimPn532::new());
+        let mut emulator = Type4Emulator::new(b"hint");
+        assert!(matches!(
+            pn532.emulate_type4(&mut emulator, [1, 2, 3]),
+            Err(Pn532Error::Timeout)
+        ));
+
+        // Телефон выбрал приложение и ушёл
+        chip.borrow_mut().initiator = Some(SimInitiator::new(vec![
+            Apdu::select_aid(&NDEF_AID).to_bytes()
+        ]));
+        assert!(!pn532.emulate_type4(&mut emulator, [1, 2, 3]).unwrap());
+    }
+}
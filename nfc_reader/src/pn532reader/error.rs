@@ -0,0 +1,210 @@
+use crate::pn532reader::frame::FrameError;
+use std::fmt;
+
+/// Ошибки драйвера PN532. Разделены так, чтобы вызывающий код мог отличить
+/// «карты нет» (`NoTarget`) от «шина/чип не отвечает» (`Transport`, `Timeout`, `NoAck`).
+#[derive(Debug)]
+pub enum Pn532Error {
+    /// Ошибка I2C/SPI/UART.
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+    /// PN532 не выставил готовность вовремя.
+    Timeout,
+    /// Вместо ACK пришло что-то другое.
+    NoAck,
+    /// Фрейм ответа не разобрался.
+    BadFrame(FrameError),
+    /// Не сошлась LCS или DCS даже после повторов.
+    ChecksumMismatch { expected: u8, got: u8 },
+    /// MIFARE Classic не принял ключ (статус 0x14). Карта после этого в HALT.
+    AuthFailed { block: u8 },
+    /// Ненулевой байт статуса в ответе InDataExchange/InCommunicateThru.
+    CardStatus(u8),
+    /// В поле нет карты.
+    NoTarget,
+    /// Ответ корректный по фреймингу, но по содержимому не тот, что ожидали.
+    InvalidResponse(&'static str),
+    /// Запись в блок/страницу, которую без `force` трогать нельзя.
+    Protected(u8),
+    /// На метке нет NDEF Capability Container (первый байт CC).
+    NotNdefFormatted(u8),
+    /// Операция не поддерживается для этого типа карты.
+    Unsupported(&'static str),
+    /// Сектора MIFARE Classic, к которым не подошёл ни один ключ.
+    UnreadableSectors(Vec<u8>),
+    /// Карта ISO14443-4 ответила на APDU статусом, отличным от 9000.
+    ApduStatus(u16),
+    /// Данные не помещаются в область данных метки.
+    PayloadTooLarge { len: usize, capacity: usize },
+    /// Прочитанный после записи блок/страница не совпал с записанным.
+    VerificationFailed(u8),
+}
+
+impl Pn532Error {
+    pub fn transport<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> Self {
+        Pn532Error::Transport(e.into())
+    }
+
+    /// Ошибки связи с самим PN532, а не с картой.
+    pub fn is_bus_error(&self) -> bool {
+        matches!(
+            self,
+            Pn532Error::Transport(_)
+                | Pn532Error::Timeout
+                | Pn532Error::NoAck
+                | Pn532Error::BadFrame(_)
+                | Pn532Error::ChecksumMismatch { .. }
+        )
+    }
+}
+
+/// Расшифровка кода ошибки PN532 (UM0701-02, табл. «Error code list»).
+/// Биты 6-7 байта статуса – флаги MI и NAD, код ошибки в младших шести битах.
+pub fn card_status_description(status: u8) -> &'static str {
+    match status & 0x3F {
+        0x00 => "success",
+        0x01 => "timeout, the target has not answered",
+        0x02 => "CRC error",
+        0x03 => "parity error",
+        0x04 => "erroneous bit count during anticollision",
+        0x05 => "framing error during MIFARE operation",
+        0x06 => "abnormal bit-collision during anticollision",
+        0x07 => "communication buffer size insufficient",
+        0x09 => "RF buffer overflow",
+        0x0A => "RF field not switched on in time",
+        0x0B => "RF protocol error",
+        0x0D => "temperature error",
+        0x0E => "internal buffer overflow",
+        0x10 => "invalid parameter",
+        0x12 => "DEP command not supported",
+        0x13 => "DEP data format error",
+        0x14 => "MIFARE authentication error",
+        0x23 => "UID check byte is wrong",
+        0x25 => "invalid device state",
+        0x26 => "operation not allowed in this configuration",
+        0x27 => "command not acceptable in current context",
+        0x29 => "target released by initiator",
+        0x2A => "card ID mismatch",
+        0x2B => "card disappeared",
+        0x2C => "NFCID3 mismatch",
+        0x2D => "over-current",
+        0x2E => "NAD missing in DEP frame",
+        _ => "unknown error",
+    }
+}
+
+impl fmt::Display for Pn532Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pn532Error::Transport(e) => write!(f, "transport error: {}", e),
+            Pn532Error::Timeout => write!(f, "timeout waiting for PN532"),
+            Pn532Error::NoAck => write!(f, "no ACK received"),
+            Pn532Error::BadFrame(e) => write!(f, "bad frame: {}", e),
+            Pn532Error::ChecksumMismatch { expected, got } => write!(
+                f,
+                "checksum mismatch: expected {:02X}, got {:02X}",
+                expected, got
+            ),
+            Pn532Error::AuthFailed { block } => {
+                write!(f, "authentication failed for block {}", block)
+            }
+            Pn532Error::CardStatus(status) => write!(
+                f,
+                "card error 0x{:02X}: {}",
+                status,
+                card_status_description(*status)
+            ),
+            Pn532Error::NoTarget => write!(f, "no card in the field"),
+            Pn532Error::InvalidResponse(msg) => write!(f, "invalid response: {}", msg),
+            Pn532Error::Protected(address) => write!(
+                f,
+                "refusing to write protected block/page {} without force",
+                address
+            ),
+            Pn532Error::NotNdefFormatted(magic) => {
+                write!(f, "tag is not NDEF formatted (CC magic {:02X})", magic)
+            }
+            Pn532Error::Unsupported(what) => write!(f, "unsupported: {}", what),
+            Pn532Error::UnreadableSectors(sectors) => {
+                write!(f, "unreadable sectors: {:?}", sectors)
+            }
+            Pn532Error::ApduStatus(sw) => write!(
+                f,
+                "card returned SW {:04X}: {}",
+                sw,
+                crate::pn532reader::apdu::status_word_description(*sw)
+            ),
+            Pn532Error::PayloadTooLarge { len, capacity } => {
+                write!(f, "payload is {} bytes, tag data area is {}", len, capacity)
+            }
+            Pn532Error::VerificationFailed(address) => write!(
+                f,
+                "verification failed: block/page {} differs after write",
+                address
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Pn532Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Pn532Error::Transport(e) => Some(e.as_ref()),
+            Pn532Error::BadFrame(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<FrameError> for Pn532Error {
+    fn from(e: FrameError) -> Self {
+        match e {
+            FrameError::DataChecksum { expected, got } => {
+                Pn532Error::ChecksumMismatch { expected, got }
+            }
+            FrameError::LengthChecksum { len, lcs } => Pn532Error::ChecksumMismatch {
+                expected: (!(len as u8)).wrapping_add(1),
+                got: lcs,
+            },
+            e => Pn532Error::BadFrame(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for Pn532Error {
+    fn from(e: std::io::Error) -> Self {
+        Pn532Error::Transport(Box::new(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_status_and_frame_errors() {
+        // Бит MI не влияет на код ошибки
+        let err = Pn532Error::CardStatus(0x54);
+        assert_eq!(
+            err.to_string(),
+            "card error 0x54: MIFARE authentication error"
+        );
+        assert!(!err.is_bus_error());
+
+        let err = Pn532Error::from(FrameError::DataChecksum {
+            expected: 0x16,
+            got: 0x17,
+        });
+        assert!(matches!(
+            err,
+            Pn532Error::ChecksumMismatch {
+                expected: 0x16,
+                got: 0x17
+            }
+        ));
+        assert!(err.is_bus_error());
+        assert!(matches!(
+            Pn532Error::from(FrameError::ApplicationError),
+            Pn532Error::BadFrame(FrameError::ApplicationError)
+        ));
+    }
+}
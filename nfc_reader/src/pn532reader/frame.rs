@@ -0,0 +1,263 @@
+use crate::pn532reader::constants::*;
+use std::fmt;
+
+pub const ACK_FRAME: [u8; 6] = [0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00];
+pub const NACK_FRAME: [u8; 6] = [0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00];
+
+/// Максимум TFI + DATA в обычном фрейме; больше – только расширенный (LEN = FF FF).
+const NORMAL_FRAME_MAX: usize = 0xFE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    /// Данных меньше, чем требует заголовок.
+    TooShort { expected: usize, got: usize },
+    /// Нет стартового кода 00 FF.
+    BadStartCode,
+    /// LEN + LCS != 0.
+    LengthChecksum { len: usize, lcs: u8 },
+    /// TFI + DATA + DCS != 0.
+    DataChecksum { expected: u8, got: u8 },
+    /// TFI не D5 – фрейм не от PN532.
+    BadTfi(u8),
+    /// Ответ не на ту команду (ожидаем код команды + 1).
+    UnexpectedCommand { expected: u8, got: u8 },
+    /// Пришёл ACK/NACK там, где ожидался ответ, или наоборот.
+    UnexpectedFrame(&'static str),
+    /// Application error frame `00 00 FF 01 FF 7F 81 00` – PN532 не принял команду.
+    ApplicationError,
+}
+
+impl FrameError {
+    /// Ошибки, после которых имеет смысл попросить повтор через NACK.
+    pub fn is_checksum(&self) -> bool {
+        matches!(
+            self,
+            FrameError::LengthChecksum { .. } | FrameError::DataChecksum { .. }
+        )
+    }
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::TooShort { expected, got } => {
+                write!(f, "frame too short: need {} bytes, got {}", expected, got)
+            }
+            FrameError::BadStartCode => write!(f, "no 00 FF start code"),
+            FrameError::LengthChecksum { len, lcs } => {
+                write!(f, "LCS checksum error (LEN {:02X}, LCS {:02X})", len, lcs)
+            }
+            FrameError::DataChecksum { expected, got } => write!(
+                f,
+                "DCS checksum error: expected {:02X}, got {:02X}",
+                expected, got
+            ),
+            FrameError::BadTfi(tfi) => write!(f, "invalid TFI {:02X}", tfi),
+            FrameError::UnexpectedCommand { expected, got } => write!(
+                f,
+                "unexpected response code {:02X}, expected {:02X}",
+                got, expected
+            ),
+            FrameError::UnexpectedFrame(kind) => write!(f, "unexpected {} frame", kind),
+            FrameError::ApplicationError => write!(f, "PN532 application error frame"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Ack,
+    Nack,
+    /// TFI + DATA уже проверены, здесь только DATA.
+    Data(Vec<u8>),
+}
+
+/// Собирает информационный фрейм. Если TFI + DATA не влезает в один байт LEN,
+/// используется расширенный формат:
+/// | PRE | SC1 | SC2 | FF | FF | LENM | LENL | LCS | TFI | DATA... | DCS | POST |
+pub fn build_frame(tfi: u8, data: &[u8]) -> Vec<u8> {
+    let len = data.len() + 1;
+    let mut frame = vec![PN532_PREAMBLE, PN532_STARTCODE1, PN532_STARTCODE2];
+    if len <= NORMAL_FRAME_MAX {
+        // LEN + LCS = 0 (по модулю 256)
+        frame.push(len as u8);
+        frame.push((!(len as u8)).wrapping_add(1));
+    } else {
+        let [lenm, lenl] = (len as u16).to_be_bytes();
+        frame.extend_from_slice(&[0xFF, 0xFF, lenm, lenl]);
+        frame.push((!lenm.wrapping_add(lenl)).wrapping_add(1));
+    }
+    frame.push(tfi);
+    frame.extend_from_slice(data);
+    // DCS: TFI + DATA + DCS = 0 (по модулю 256)
+    let sum = data.iter().fold(tfi, |acc, b| acc.wrapping_add(*b));
+    frame.push((!sum).wrapping_add(1));
+    frame.push(PN532_POSTAMBLE);
+    frame
+}
+
+/// Разбирает фрейм PN532 -> хост. Преамбула может отсутствовать или быть длиннее,
+/// поэтому ищем стартовый код 00 FF. Хвост после POST (мусор I2C) игнорируется.
+pub fn parse_frame(buffer: &[u8]) -> Result<Frame, FrameError> {
+    let start = buffer
+        .windows(2)
+        .position(|w| w == [PN532_STARTCODE1, PN532_STARTCODE2])
+        .ok_or(FrameError::BadStartCode)?;
+    let frame = &buffer[start + 2..];
+    if frame.len() < 2 {
+        return Err(FrameError::TooShort {
+            expected: start + 4,
+            got: buffer.len(),
+        });
+    }
+
+    match (frame[0], frame[1]) {
+        (0x00, 0xFF) => return Ok(Frame::Ack),
+        (0xFF, 0x00) => return Ok(Frame::Nack),
+        _ => {}
+    }
+
+    let (len, body_start) = if frame[0] == 0xFF && frame[1] == 0xFF {
+        // Расширенный фрейм: LENM LENL LCS
+        if frame.len() < 5 {
+            return Err(FrameError::TooShort {
+                expected: start + 7,
+                got: buffer.len(),
+            });
+        }
+        let (lenm, lenl, lcs) = (frame[2], frame[3], frame[4]);
+        if lenm.wrapping_add(lenl).wrapping_add(lcs) != 0 {
+            return Err(FrameError::LengthChecksum {
+                len: u16::from_be_bytes([lenm, lenl]) as usize,
+                lcs,
+            });
+        }
+        (u16::from_be_bytes([lenm, lenl]) as usize, 5)
+    } else {
+        let (len, lcs) = (frame[0], frame[1]);
+        if len.wrapping_add(lcs) != 0 {
+            return Err(FrameError::LengthChecksum {
+                len: len as usize,
+                lcs,
+            });
+        }
+        (len as usize, 2)
+    };
+
+    // TFI + DATA, затем DCS и POST
+    let needed = body_start + len + 2;
+    if len == 0 || frame.len() < needed {
+        return Err(FrameError::TooShort {
+            expected: start + 2 + needed,
+            got: buffer.len(),
+        });
+    }
+    let body = &frame[body_start..body_start + len];
+    let dcs = frame[body_start + len];
+    let sum = body.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+    if sum.wrapping_add(dcs) != 0 {
+        return Err(FrameError::DataChecksum {
+            expected: (!sum).wrapping_add(1),
+            got: dcs,
+        });
+    }
+
+    // Application error frame: LEN = 1, TFI = 7F
+    if len == 1 && body[0] == 0x7F {
+        return Err(FrameError::ApplicationError);
+    }
+    if body[0] != PN532_PN532TOHOST {
+        return Err(FrameError::BadTfi(body[0]));
+    }
+    Ok(Frame::Data(body[1..].to_vec()))
+}
+
+/// Проверяет, что `data` – ответ на `command`, и отрезает код ответа.
+pub fn strip_response_code(command: u8, data: &[u8]) -> Result<Vec<u8>, FrameError> {
+    let expected = command.wrapping_add(1);
+    match data.first() {
+        Some(&code) if code == expected => Ok(data[1..].to_vec()),
+        Some(&code) => Err(FrameError::UnexpectedCommand {
+            expected,
+            got: code,
+        }),
+        None => Err(FrameError::TooShort {
+            expected: 1,
+            got: 0,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ack_nack_and_data() {
+        assert_eq!(parse_frame(&ACK_FRAME), Ok(Frame::Ack));
+        assert_eq!(parse_frame(&NACK_FRAME), Ok(Frame::Nack));
+        let frame = build_frame(PN532_PN532TOHOST, &[0x15]);
+        assert_eq!(
+            frame,
+            [0x00, 0x00, 0xFF, 0x02, 0xFE, 0xD5, 0x15, 0x16, 0x00]
+        );
+        // Лишние байты после POST (чтение I2C с запасом) не мешают
+        let mut padded = frame.clone();
+        padded.extend_from_slice(&[0x00; 8]);
+        assert_eq!(parse_frame(&padded), Ok(Frame::Data(vec![0x15])));
+    }
+
+    #[test]
+    fn extended_frame_round_trip() {
+        let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        let frame = build_frame(PN532_PN532TOHOST, &data);
+        assert_eq!(&frame[3..5], &[0xFF, 0xFF]);
+        assert_eq!(&frame[5..7], &[0x01, 0x2D]);
+        assert_eq!(parse_frame(&frame), Ok(Frame::Data(data)));
+    }
+
+    #[test]
+    fn reports_each_error() {
+        assert_eq!(
+            parse_frame(&[0x00, 0x00, 0xFF, 0x01, 0xFF, 0x7F, 0x81, 0x00]),
+            Err(FrameError::ApplicationError)
+        );
+        assert_eq!(parse_frame(&[0x00; 8]), Err(FrameError::BadStartCode));
+
+        let mut frame = build_frame(PN532_PN532TOHOST, &[0x03, 0x32]);
+        assert!(matches!(
+            parse_frame(&frame[..6]),
+            Err(FrameError::TooShort { .. })
+        ));
+
+        frame[4] ^= 0x01;
+        assert!(matches!(
+            parse_frame(&frame),
+            Err(FrameError::LengthChecksum { .. })
+        ));
+        frame[4] ^= 0x01;
+
+        let dcs = frame.len() - 2;
+        frame[dcs] ^= 0x01;
+        let err = parse_frame(&frame).unwrap_err();
+        assert!(err.is_checksum());
+        assert!(matches!(err, FrameError::DataChecksum { got, .. } if got == frame[dcs]));
+
+        let host = build_frame(PN532_HOSTTOPN532, &[0x02]);
+        assert_eq!(parse_frame(&host), Err(FrameError::BadTfi(0xD4)));
+    }
+
+    #[test]
+    fn checks_response_code() {
+        assert_eq!(strip_response_code(0x4A, &[0x4B, 0x00]), Ok(vec![0x00]));
+        assert_eq!(
+            strip_response_code(0x4A, &[0x41, 0x00]),
+            Err(FrameError::UnexpectedCommand {
+                expected: 0x4B,
+                got: 0x41
+            })
+        );
+    }
+}
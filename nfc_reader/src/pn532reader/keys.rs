@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+pub type MifareKey = [u8; 6];
+
+/// Ключи, которые чаще всего встречаются на картах: транспортный FF..FF,
+/// MAD (A0..A5 / B0..B5), NDEF (D3 F7..) и несколько распространённых.
+pub const WELL_KNOWN_KEYS: [MifareKey; 8] = [
+    [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+    [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5],
+    [0xB0, 0xB1, 0xB2, 0xB3, 0xB4, 0xB5],
+    [0xD3, 0xF7, 0xD3, 0xF7, 0xD3, 0xF7],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x4D, 0x3A, 0x99, 0xC3, 0x51, 0xDD],
+    [0x1A, 0x98, 0x2C, 0x7E, 0x45, 0x9A],
+    [0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyType {
+    A,
+    B,
+}
+
+impl KeyType {
+    /// Код команды MIFARE AUTH: 0x60 – ключ A, 0x61 – ключ B.
+    pub fn auth_command(&self) -> u8 {
+        match self {
+            KeyType::A => 0x60,
+            KeyType::B => 0x61,
+        }
+    }
+
+    pub fn from_auth_command(command: u8) -> Option<Self> {
+        match command {
+            0x60 => Some(KeyType::A),
+            0x61 => Some(KeyType::B),
+            _ => None,
+        }
+    }
+
+    fn other(&self) -> Self {
+        match self {
+            KeyType::A => KeyType::B,
+            KeyType::B => KeyType::A,
+        }
+    }
+}
+
+/// Известные ключи MIFARE Classic: `WELL_KNOWN_KEYS` и ключи из файла.
+///
+/// Файл ключей – по одному ключу на строку, `#` – комментарий:
+/// ```text
+/// FFFFFFFFFFFF        # общий ключ, пробуется как A и как B во всех секторах
+/// 5 A A0A1A2A3A4A5    # ключ A сектора 5
+/// 5 B 0123456789AB    # ключ B сектора 5
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyStore {
+    common: Vec<MifareKey>,
+    sectors: HashMap<u8, Vec<(KeyType, MifareKey)>>,
+    /// Каким типом ключа пробовать общие ключи сначала.
+    preferred: KeyType,
+}
+
+impl Default for KeyStore {
+    fn default() -> Self {
+        KeyStore {
+            common: WELL_KNOWN_KEYS.to_vec(),
+            sectors: HashMap::new(),
+            preferred: KeyType::A,
+        }
+    }
+}
+
+fn parse_key(text: &str) -> Result<MifareKey, String> {
+    let bytes = hex::decode(text).map_err(|e| format!("invalid key {}: {}", text, e))?;
+    bytes
+        .try_into()
+        .map_err(|_| format!("key {} must be 6 bytes", text))
+}
+
+impl KeyStore {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut store = KeyStore::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let error = |e: String| format!("line {}: {}", number + 1, e);
+            match fields.as_slice() {
+                [] => {}
+                [key] => store.add_common(parse_key(key).map_err(error)?),
+                [sector, key_type, key] => {
+                    let sector = sector
+                        .parse()
+                        .map_err(|_| error(format!("invalid sector {}", sector)))?;
+                    let key_type = match key_type.to_ascii_uppercase().as_str() {
+                        "A" => KeyType::A,
+                        "B" => KeyType::B,
+                        other => return Err(error(format!("invalid key type {}", other))),
+                    };
+                    store.add_sector_key(sector, key_type, parse_key(key).map_err(error)?);
+                }
+                _ => return Err(error("expected `KEY` or `SECTOR A|B KEY`".to_string())),
+            }
+        }
+        Ok(store)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(KeyStore::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn with_preferred(mut self, key_type: KeyType) -> Self {
+        self.preferred = key_type;
+        self
+    }
+
+    pub fn add_common(&mut self, key: MifareKey) {
+        if !self.common.contains(&key) {
+            self.common.push(key);
+        }
+    }
+
+    pub fn add_sector_key(&mut self, sector: u8, key_type: KeyType, key: MifareKey) {
+        let keys = self.sectors.entry(sector).or_default();
+        if !keys.contains(&(key_type, key)) {
+            keys.push((key_type, key));
+        }
+    }
+
+    /// В каком порядке пробовать ключи для сектора: сначала ключи этого сектора,
+    /// затем общие – каждый предпочитаемым типом, потом другим.
+    pub fn candidates(&self, sector: u8) -> Vec<(KeyType, MifareKey)> {
+        let mut keys = self.sectors.get(&sector).cloned().unwrap_or_default();
+        for key_type in [self.preferred, self.preferred.other()] {
+            for key in &self.common {
+                if !keys.contains(&(key_type, *key)) {
+                    keys.push((key_type, *key));
+                }
+            }
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_key_file() {
+        let store = KeyStore::parse(
+            "# keys\nffffffffffff\n\n5 b 0123456789AB  # sector 5\n5 A A0A1A2A3A4A5\n112233445566\n",
+        )
+        .unwrap()
+        .with_preferred(KeyType::B);
+        let candidates = store.candidates(5);
+        assert_eq!(
+            candidates[..3],
+            [
+                (KeyType::B, [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB]),
+                (KeyType::A, [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5]),
+                (KeyType::B, [0xFF; 6]),
+            ]
+        );
+        // Известные ключи + один новый из файла, каждый как B и как A
+        assert_eq!(store.candidates(1).len(), 18);
+        assert_eq!(
+            store.candidates(1)[8],
+            (KeyType::B, [0x11, 0x22, 0x33, 0x44, 0x55, 0x66])
+        );
+
+        assert!(KeyStore::parse("FFFF").is_err());
+        assert!(KeyStore::parse("1 C FFFFFFFFFFFF").is_err());
+        assert!(KeyStore::parse("x A FFFFFFFFFFFF").is_err());
+    }
+}
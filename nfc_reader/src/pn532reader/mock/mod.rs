@@ -0,0 +1,562 @@
+pub mod tags;
+
+pub use tags::{ClassicTag, NtagTag, Type4Tag, VirtualTag};
+
+use crate::pn532reader::apdu::{MAX_EXCHANGE_DATA, MI_FLAG};
+use crate::pn532reader::constants::*;
+use crate::pn532reader::device::PN532;
+use crate::pn532reader::error::Pn532Error;
+use crate::pn532reader::frame::{build_frame, ACK_FRAME, NACK_FRAME};
+use crate::pn532reader::power::ResetPin;
+use crate::pn532reader::rfconfig::{CFG_MAX_RETRIES, RETRY_FOREVER};
+use crate::pn532reader::transport::Transport;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::rc::Rc;
+
+/// Syntax error frame – PN532 не понял команду.
+const ERROR_FRAME: [u8; 8] = [0x00, 0x00, 0xFF, 0x01, 0xFF, 0x7F, 0x81, 0x00];
+
+/// Телефон, читающий PN532 в режиме карты: отправляет `commands` по одной,
+/// ответы складывает в `responses`. Команды кончились – телефон ушёл из поля.
+#[derive(Debug, Clone, Default)]
+pub struct SimInitiator {
+    pub commands: VecDeque<Vec<u8>>,
+    pub responses: Vec<Vec<u8>>,
+}
+
+impl SimInitiator {
+    pub fn new(commands: Vec<Vec<u8>>) -> Self {
+        SimInitiator {
+            commands: commands.into(),
+            responses: Vec::new(),
+        }
+    }
+}
+
+/// Программная модель PN532 на I2C: принимает фреймы хоста, проверяет
+/// LEN/LCS/DCS, отвечает ACK и фреймом ответа, отдаёт статусный байт.
+/// Метки в поле – `tag` (Tg 1) и `second_tag` (Tg 2, без первой не видна);
+/// их можно подменить или убрать. `initiator` – телефон для режима карты.
+#[derive(Default)]
+pub struct SimPn532 {
+    pub tag: Option<VirtualTag>,
+    pub second_tag: Option<VirtualTag>,
+    pub initiator: Option<SimInitiator>,
+    /// Чип завис: не отвечает ни на что до сброса через RSTPDN.
+    pub wedged: bool,
+    /// После PowerDown фреймы игнорируются, пока шина не разбудит чип.
+    pub powered_down: bool,
+    /// Последние данные RFConfiguration по CfgItem; сбрасываются с чипом.
+    pub rf_settings: BTreeMap<u8, Vec<u8>>,
+    /// Данные (TFI не включается) всех принятых команд – для проверок в тестах.
+    pub received: Vec<Vec<u8>>,
+    /// Испортить DCS в следующем ответе.
+    pub corrupt_next_response: bool,
+    /// Ответ, выставленный одновременно с ACK-отменой хоста: отмена его уже
+    /// не стирает.
+    pub response_in_flight: Option<Vec<u8>>,
+    output: VecDeque<Vec<u8>>,
+    last_response: Option<Vec<u8>>,
+    /// Части команды, пришедшие с битом MI.
+    pending_command: Vec<u8>,
+    /// Хвост ответа метки, не влезший в одну InDataExchange.
+    pending_reply: Vec<u8>,
+}
+
+impl SimPn532 {
+    pub fn new() -> Self {
+        SimPn532::default()
+    }
+
+    pub fn with_tag(tag: VirtualTag) -> Self {
+        SimPn532 {
+            tag: Some(tag),
+            ..SimPn532::default()
+        }
+    }
+
+    /// Импульс на RSTPDN: чип перезагружается и забывает незаконченные обмены.
+    pub fn reset(&mut self) {
+        self.wedged = false;
+        self.powered_down = false;
+        self.output.clear();
+        self.last_response = None;
+        self.pending_command.clear();
+        self.pending_reply.clear();
+        self.rf_settings.clear();
+    }
+
+    /// Обращение по шине выводит чип из PowerDown.
+    pub fn wake(&mut self) {
+        self.powered_down = false;
+    }
+
+    /// Запись хоста по I2C.
+    pub fn write(&mut self, frame: &[u8]) {
+        if self.wedged || self.powered_down {
+            return;
+        }
+        if frame == ACK_FRAME {
+            // ACK от хоста прерывает текущую команду
+            self.output.clear();
+            self.output.extend(self.response_in_flight.take());
+            return;
+        }
+        if frame == NACK_FRAME {
+            if let Some(response) = self.last_response.clone() {
+                self.output.push_back(response);
+            }
+            return;
+        }
+        let data = match parse_host_frame(frame) {
+            Some(data) => data,
+            // Фрейм с ошибкой контрольной суммы PN532 молча игнорирует
+            None => return,
+        };
+        self.received.push(data.clone());
+        self.output.clear();
+        self.output.push_back(ACK_FRAME.to_vec());
+        if data.first() == Some(&PN532_COMMAND_TGINITASTARGET) && self.initiator.is_none() {
+            // Инициатора нет – PN532 ждёт его, пока хост не отменит команду ACK-ом
+            return;
+        }
+        if data.first() == Some(&PN532_COMMAND_INAUTOPOLL)
+            && data.get(1) == Some(&0xFF)
+            && self.tag.is_none()
+        {
+            // Бесконечный опрос пустого поля – ответа не будет до ACK от хоста
+            return;
+        }
+        if data.first() == Some(&PN532_COMMAND_INLISTPASSIVETARGET)
+            && self
+                .rf_settings
+                .get(&CFG_MAX_RETRIES)
+                .and_then(|cfg| cfg.get(2))
+                == Some(&RETRY_FOREVER)
+            && self.tag.is_none()
+        {
+            // MxRtyPassiveActivation = 0xFF: PN532 ищет карту, пока её не поднесут
+            return;
+        }
+
+        let response = match self.execute(&data) {
+            Some(payload) => build_response_frame(&payload),
+            None => ERROR_FRAME.to_vec(),
+        };
+        self.last_response = Some(response.clone());
+        let mut sent = response;
+        if self.corrupt_next_response {
+            // Портится только передаваемая копия: по NACK уйдёт правильный фрейм
+            self.corrupt_next_response = false;
+            let dcs = sent.len() - 2;
+            sent[dcs] = sent[dcs].wrapping_add(1);
+        }
+        self.output.push_back(sent);
+    }
+
+    /// Чтение хоста по I2C: статусный байт (бит 0 – готов), затем фрейм.
+    /// Чтение одного байта – только опрос статуса, фрейм не забирается.
+    pub fn read(&mut self, len: usize) -> Vec<u8> {
+        let mut out = vec![0u8; len];
+        if len == 0 {
+            return out;
+        }
+        if self.output.is_empty() {
+            return out;
+        }
+        out[0] = 0x01;
+        if len > 1 {
+            let frame = self.output.pop_front().unwrap();
+            let n = frame.len().min(len - 1);
+            out[1..1 + n].copy_from_slice(&frame[..n]);
+        }
+        out
+    }
+
+    /// Статус и очередная часть ответа метки; MI – если осталось ещё.
+    fn push_reply_chunk(&mut self, response: &mut Vec<u8>, status: u8) {
+        let n = self.pending_reply.len().min(MAX_EXCHANGE_DATA);
+        let chunk: Vec<u8> = self.pending_reply.drain(..n).collect();
+        if self.pending_reply.is_empty() {
+            response.push(status);
+        } else {
+            response.push(status | MI_FLAG);
+        }
+        response.extend_from_slice(&chunk);
+    }
+
+    fn execute(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        let command = *data.first()?;
+        let mut response = vec![command.wrapping_add(1)];
+        match command {
+            PN532_COMMAND_GETFIRMWAREVERSION => {
+                // PN532 v1.6, поддерживает ISO14443A/B и ISO18092
+                response.extend_from_slice(&[0x32, 0x01, 0x06, 0x07]);
+            }
+            PN532_COMMAND_SAMCONFIGURATION => {}
+            PN532_COMMAND_DIAGNOSE => {
+                // Поддержан только тест 0x06: отвечает ли активная карта ISO14443-4
+                if data.get(1) != Some(&0x06) {
+                    return None;
+                }
+                let present = self
+                    .tag
+                    .as_ref()
+                    .is_some_and(|tag| tag.ats().is_some() && !tag.is_halted());
+                response.push(if present { tags::STATUS_OK } else { 0x01 });
+            }
+            PN532_COMMAND_POWERDOWN => {
+                if data.len() < 2 {
+                    return None;
+                }
+                // Ответ уходит до засыпания
+                self.powered_down = true;
+                response.push(tags::STATUS_OK);
+            }
+            PN532_COMMAND_RFCONFIGURATION => {
+                let (&item, config) = data.get(1..)?.split_first()?;
+                let expected = match item {
+                    0x01 | 0x04 => 1,
+                    0x02 | 0x05 => 3,
+                    0x0A => 11,
+                    0x0B => 8,
+                    0x0C => 3,
+                    0x0D => 9,
+                    _ => return None,
+                };
+                if config.len() != expected {
+                    return None;
+                }
+                self.rf_settings.insert(item, config.to_vec());
+            }
+            PN532_COMMAND_INLISTPASSIVETARGET => {
+                if data.len() < 3 || !(1..=2).contains(&data[1]) || data[2] > 0x04 {
+                    return None;
+                }
+                // Виртуальные метки только type A, на других модуляциях поле пустое
+                let tags = if data[2] == 0x00 && self.tag.is_some() {
+                    [self.tag.as_mut(), self.second_tag.as_mut()]
+                } else {
+                    [None, None]
+                };
+                // InitiatorData для type A – UID карты, которую нужно выбрать
+                let uid_filter = &data[3..];
+                let found: Vec<&mut VirtualTag> = tags
+                    .into_iter()
+                    .flatten()
+                    .filter(|tag| !tag.left_field())
+                    .filter(|tag| uid_filter.is_empty() || tag.uid() == uid_filter)
+                    .take(data[1] as usize)
+                    .collect();
+                response.push(found.len() as u8);
+                for (i, tag) in found.into_iter().enumerate() {
+                    response.extend_from_slice(&activate_target(i as u8 + 1, tag));
+                }
+            }
+            PN532_COMMAND_INAUTOPOLL => {
+                // PollNr, Period (1..15), Type1..TypeN (N = 1..15)
+                if data.len() < 4 || !(1..=15).contains(&data[2]) || data.len() > 18 {
+                    return None;
+                }
+                let tags = if self.tag.is_some() {
+                    [self.tag.as_mut(), self.second_tag.as_mut()]
+                } else {
+                    [None, None]
+                };
+                let mut found: Vec<&mut VirtualTag> = tags.into_iter().flatten().collect();
+                // Опрос идёт по типам по очереди; первый тип, под который подошла
+                // хоть одна метка, – его цели и отдаются
+                let poll_type = data[3..]
+                    .iter()
+                    .copied()
+                    .find(|&poll_type| found.iter().any(|tag| polls_as(poll_type, tag)));
+                found.retain(|tag| poll_type.is_some_and(|poll_type| polls_as(poll_type, tag)));
+                response.push(found.len() as u8);
+                for (i, tag) in found.into_iter().enumerate() {
+                    let target = activate_target(i as u8 + 1, tag);
+                    response.push(poll_type.unwrap());
+                    response.push(target.len() as u8);
+                    response.extend_from_slice(&target);
+                }
+            }
+            PN532_COMMAND_INDATAEXCHANGE => {
+                if data.len() < 2 {
+                    return None;
+                }
+                let more = data[1] & MI_FLAG != 0;
+                // Пустая команда без MI – хост дочитывает ответ после MI
+                if data.len() == 2 && !more && !self.pending_reply.is_empty() {
+                    self.push_reply_chunk(&mut response, tags::STATUS_OK);
+                    return Some(response);
+                }
+                self.pending_command.extend_from_slice(&data[2..]);
+                if more {
+                    response.push(tags::STATUS_OK);
+                    return Some(response);
+                }
+                let command = std::mem::take(&mut self.pending_command);
+                let tag = match data[1] {
+                    0x01 => self.tag.as_mut(),
+                    0x02 => self.second_tag.as_mut(),
+                    _ => None,
+                };
+                let (status, reply) = match tag {
+                    Some(tag) => tag.data_exchange(&command),
+                    // 0x27 – нет такой активной цели
+                    _ => (0x27, Vec::new()),
+                };
+                self.pending_reply = reply;
+                self.push_reply_chunk(&mut response, status);
+            }
+            PN532_COMMAND_INRELEASE | PN532_COMMAND_INSELECT => {
+                if data.len() < 2 {
+                    return None;
+                }
+                let tags = [(0x01, self.tag.as_mut()), (0x02, self.second_tag.as_mut())];
+                let mut status = 0x27;
+                for (tg, tag) in tags {
+                    if let Some(tag) = tag.filter(|_| data[1] == 0x00 || data[1] == tg) {
+                        if command == PN532_COMMAND_INRELEASE {
+                            tag.halt();
+                        } else {
+                            tag.activate();
+                        }
+                        status = 0x00;
+                    }
+                }
+                response.push(status);
+            }
+            PN532_COMMAND_TGINITASTARGET => {
+                let initiator = self.initiator.as_mut()?;
+                // Mode: 106 kbps, ISO14443-4 PICC; затем первая команда инициатора
+                response.push(0x08);
+                response.extend(initiator.commands.pop_front().unwrap_or_default());
+            }
+            PN532_COMMAND_TGGETDATA | PN532_COMMAND_TGSETDATA => {
+                let Some(initiator) = self.initiator.as_mut() else {
+                    // 0x25 – PN532 не в режиме карты
+                    response.push(0x25);
+                    return Some(response);
+                };
+                if command == PN532_COMMAND_TGSETDATA {
+                    initiator.responses.push(data[1..].to_vec());
+                    response.push(tags::STATUS_OK);
+                } else {
+                    match initiator.commands.pop_front() {
+                        Some(next) => {
+                            response.push(tags::STATUS_OK);
+                            response.extend(next);
+                        }
+                        // 0x29 – инициатор освободил цель
+                        None => response.push(0x29),
+                    }
+                }
+            }
+            PN532_COMMAND_INCOMMUNICATETHRU => {
+                let (status, reply) = match self.tag.as_mut() {
+                    Some(tag) => tag.communicate_thru(&data[1..]),
+                    None => (tags::STATUS_TIMEOUT, Vec::new()),
+                };
+                response.push(status);
+                response.extend_from_slice(&reply);
+            }
+            _ => return None,
+        }
+        Some(response)
+    }
+}
+
+/// Разбирает фрейм хоста (обычный или расширенный), возвращает данные после TFI
+/// или `None`, если он битый.
+fn parse_host_frame(frame: &[u8]) -> Option<Vec<u8>> {
+    if frame.len() < 8 || frame[..3] != [PN532_PREAMBLE, PN532_STARTCODE1, PN532_STARTCODE2] {
+        return None;
+    }
+    let (len, body_start) = if frame[3] == 0xFF && frame[4] == 0xFF {
+        if frame[5].wrapping_add(frame[6]).wrapping_add(frame[7]) != 0 {
+            return None;
+        }
+        (u16::from_be_bytes([frame[5], frame[6]]) as usize, 8)
+    } else {
+        if frame[3].wrapping_add(frame[4]) != 0 {
+            return None;
+        }
+        (frame[3] as usize, 5)
+    };
+    if len == 0 || frame.len() < body_start + len + 2 {
+        return None;
+    }
+    let body = &frame[body_start..body_start + len];
+    if body[0] != PN532_HOSTTOPN532 {
+        return None;
+    }
+    let sum = body.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+    if sum.wrapping_add(frame[body_start + len]) != 0 {
+        return None;
+    }
+    Some(body[1..].to_vec())
+}
+
+fn build_response_frame(payload: &[u8]) -> Vec<u8> {
+    build_frame(PN532_PN532TOHOST, payload)
+}
+
+/// Транспорт поверх `SimPn532`, ведёт себя как `I2cTransport`. Состояние чипа
+/// общее, чтобы тест мог менять метку в поле после передачи транспорта в `PN532`.
+pub struct MockTransport {
+    chip: Rc<RefCell<SimPn532>>,
+}
+
+impl MockTransport {
+    pub fn new(chip: Rc<RefCell<SimPn532>>) -> Self {
+        MockTransport { chip }
+    }
+}
+
+impl Transport for MockTransport {
+    fn wake_up(&mut self) -> Result<(), Pn532Error> {
+        self.chip.borrow_mut().wake();
+        Ok(())
+    }
+
+    fn wait_ready(&mut self, _timeout_ms: u32) -> Result<bool, Pn532Error> {
+        Ok(self.chip.borrow_mut().read(1)[0] & 0x01 == 0x01)
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), Pn532Error> {
+        self.chip.borrow_mut().write(frame);
+        Ok(())
+    }
+
+    fn read_frame(&mut self, len: usize) -> Result<Vec<u8>, Pn532Error> {
+        let mut buffer = self.chip.borrow_mut().read(len + 1);
+        buffer.remove(0);
+        Ok(buffer)
+    }
+}
+
+/// RSTPDN симулятора.
+pub struct MockResetPin {
+    chip: Rc<RefCell<SimPn532>>,
+}
+
+impl MockResetPin {
+    pub fn new(chip: Rc<RefCell<SimPn532>>) -> Self {
+        MockResetPin { chip }
+    }
+}
+
+impl ResetPin for MockResetPin {
+    fn set_reset(&mut self, asserted: bool) -> Result<(), Pn532Error> {
+        if asserted {
+            self.chip.borrow_mut().reset();
+        }
+        Ok(())
+    }
+}
+
+/// `PN532` поверх симулятора + ручка на сам симулятор.
+/// Активирует метку и возвращает её данные в формате InListPassiveTarget.
+fn activate_target(tg: u8, tag: &mut VirtualTag) -> Vec<u8> {
+    tag.activate();
+    let uid = tag.uid();
+    let mut target = vec![tg];
+    target.extend_from_slice(&tag.sens_res().to_be_bytes());
+    target.push(tag.sel_res());
+    target.push(uid.len() as u8);
+    target.extend_from_slice(&uid);
+    if let Some(ats) = tag.ats() {
+        target.extend_from_slice(&ats);
+    }
+    target
+}
+
+/// Найдёт ли InAutoPoll метку, опрашивая тип `poll_type`. Все виртуальные
+/// метки – type A 106 кбит/с.
+fn polls_as(poll_type: u8, tag: &VirtualTag) -> bool {
+    match poll_type {
+        0x00 => true,
+        0x10 => tag.sel_res() & 0x20 == 0,
+        0x20 => tag.sel_res() & 0x20 != 0,
+        _ => false,
+    }
+}
+
+pub fn connect(chip: SimPn532) -> (PN532, Rc<RefCell<SimPn532>>) {
+    let chip = Rc::new(RefCell::new(chip));
+    let pn532 = PN532::with_transport(Box::new(MockTransport::new(chip.clone())));
+    (pn532, chip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host_frame(data: &[u8]) -> Vec<u8> {
+        build_frame(PN532_HOSTTOPN532, data)
+    }
+
+    #[test]
+    fn ack_then_response_with_status_byte() {
+        let mut chip = SimPn532::new();
+        assert_eq!(chip.read(1), [0x00]);
+
+        chip.write(&host_frame(&[PN532_COMMAND_GETFIRMWAREVERSION]));
+        assert_eq!(chip.read(1), [0x01]);
+        assert_eq!(chip.read(7)[1..], ACK_FRAME);
+        let response = chip.read(16);
+        assert_eq!(response[0], 0x01);
+        assert_eq!(
+            response[1..14],
+            [0x00, 0x00, 0xFF, 0x06, 0xFA, 0xD5, 0x03, 0x32, 0x01, 0x06, 0x07, 0xE8, 0x00]
+        );
+        assert_eq!(chip.read(1), [0x00]);
+    }
+
+    #[test]
+    fn bad_checksum_is_ignored_and_unknown_command_errors() {
+        let mut chip = SimPn532::new();
+        let mut frame = host_frame(&[PN532_COMMAND_GETFIRMWAREVERSION]);
+        let dcs = frame.len() - 2;
+        frame[dcs] ^= 0xFF;
+        chip.write(&frame);
+        assert_eq!(chip.read(1), [0x00]);
+
+        chip.write(&host_frame(&[0x7E]));
+        chip.read(7);
+        assert_eq!(chip.read(9)[1..], ERROR_FRAME);
+    }
+
+    #[test]
+    fn nack_repeats_last_response() {
+        let mut chip = SimPn532::new();
+        chip.write(&host_frame(&[PN532_COMMAND_SAMCONFIGURATION, 0x01]));
+        chip.read(7);
+        let first = chip.read(10);
+        chip.write(&NACK_FRAME);
+        assert_eq!(chip.read(10), first);
+    }
+
+    #[test]
+    fn loads_tags_from_dump_files() {
+        let dir = std::env::temp_dir().join(format!("nfc_reader_mock_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let classic = dir.join("card.mfd");
+        std::fs::write(&classic, ClassicTag::blank_1k([1, 2, 3, 4]).memory).unwrap();
+        let ntag = dir.join("tag.bin");
+        let blank = NtagTag::blank(crate::pn532reader::ntag::NtagModel::Ntag216, [7; 7]);
+        std::fs::write(&ntag, &blank.memory).unwrap();
+
+        let tag = VirtualTag::from_dump_file(&classic).unwrap();
+        assert!(matches!(tag, VirtualTag::Classic(_)));
+        assert_eq!(tag.uid(), [1, 2, 3, 4]);
+        let tag = VirtualTag::from_dump_file(&ntag).unwrap();
+        assert_eq!(tag.uid(), [7; 7]);
+        assert_eq!(tag.sens_res(), 0x0044);
+
+        std::fs::write(&ntag, [0u8; 100]).unwrap();
+        assert!(VirtualTag::from_dump_file(&ntag).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
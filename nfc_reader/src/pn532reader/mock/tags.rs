@@ -0,0 +1,424 @@
+use crate::pn532reader::emulation::Type4Emulator;
+use crate::pn532reader::ntag::NtagModel;
+use std::path::Path;
+
+// Коды ошибок PN532 (байт статуса InDataExchange / InCommunicateThru)
+pub const STATUS_OK: u8 = 0x00;
+pub const STATUS_TIMEOUT: u8 = 0x01;
+pub const STATUS_MIFARE_AUTH: u8 = 0x14;
+
+/// Результат обмена с меткой: байт статуса PN532 + данные ответа.
+pub type TagReply = (u8, Vec<u8>);
+
+/// MIFARE Classic Mini/1K/4K. Память – обычный дамп `.mfd`,
+/// ключи берутся из трейлеров, access bits не проверяются.
+#[derive(Debug, Clone)]
+pub struct ClassicTag {
+    pub memory: Vec<u8>,
+    /// Блоки, READ которых карта отвергает (как при запрете в access bits).
+    pub unreadable_blocks: Vec<usize>,
+    /// После первого NAK карту уносят из поля – повторно она не активируется.
+    pub leaves_field_on_nak: bool,
+    authenticated: Option<usize>,
+    halted: bool,
+    left_field: bool,
+}
+
+impl ClassicTag {
+    /// Размер дампа: Mini – 320 байт, 1K – 1024, 4K – 4096.
+    pub fn from_bytes(memory: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
+        if !matches!(memory.len(), 320 | 1024 | 4096) {
+            return Err(format!("Unexpected MIFARE Classic dump size {}", memory.len()).into());
+        }
+        Ok(ClassicTag {
+            memory,
+            unreadable_blocks: Vec::new(),
+            leaves_field_on_nak: false,
+            authenticated: None,
+            halted: false,
+            left_field: false,
+        })
+    }
+
+    /// Чистая карта 1K: транспортные ключи FF..FF, access bits FF 07 80 69.
+    pub fn blank_1k(uid: [u8; 4]) -> Self {
+        let mut memory = vec![0u8; 1024];
+        memory[..4].copy_from_slice(&uid);
+        memory[4] = uid.iter().fold(0, |acc, b| acc ^ b); // BCC
+        memory[5..8].copy_from_slice(&[0x08, 0x04, 0x00]);
+        for sector in 0..16 {
+            let trailer = (sector * 4 + 3) * 16;
+            memory[trailer..trailer + 6].copy_from_slice(&[0xFF; 6]);
+            memory[trailer + 6..trailer + 10].copy_from_slice(&[0xFF, 0x07, 0x80, 0x69]);
+            memory[trailer + 10..trailer + 16].copy_from_slice(&[0xFF; 6]);
+        }
+        ClassicTag {
+            memory,
+            unreadable_blocks: Vec::new(),
+            leaves_field_on_nak: false,
+            authenticated: None,
+            halted: false,
+            left_field: false,
+        }
+    }
+
+    pub fn uid(&self) -> Vec<u8> {
+        self.memory[..4].to_vec()
+    }
+
+    pub fn sel_res(&self) -> u8 {
+        match self.memory.len() {
+            320 => 0x09,
+            4096 => 0x18,
+            _ => 0x08,
+        }
+    }
+
+    pub fn sens_res(&self) -> u16 {
+        if self.memory.len() == 4096 {
+            0x0002
+        } else {
+            0x0004
+        }
+    }
+
+    fn block_count(&self) -> usize {
+        self.memory.len() / 16
+    }
+
+    fn sector_of(block: usize) -> usize {
+        if block < 128 {
+            block / 4
+        } else {
+            32 + (block - 128) / 16
+        }
+    }
+
+    fn trailer_of(sector: usize) -> usize {
+        if sector < 32 {
+            sector * 4 + 3
+        } else {
+            128 + (sector - 32) * 16 + 15
+        }
+    }
+
+    /// Меняет ключи сектора так, будто трейлер перезаписали.
+    pub fn set_keys(&mut self, sector: usize, key_a: [u8; 6], key_b: [u8; 6]) {
+        let trailer = Self::trailer_of(sector) * 16;
+        self.memory[trailer..trailer + 6].copy_from_slice(&key_a);
+        self.memory[trailer + 10..trailer + 16].copy_from_slice(&key_b);
+    }
+
+    fn transceive(&mut self, data: &[u8]) -> TagReply {
+        if self.halted || data.is_empty() {
+            return (STATUS_TIMEOUT, Vec::new());
+        }
+        match data[0] {
+            0x60 | 0x61 if data.len() >= 12 => {
+                let block = data[1] as usize;
+                if block >= self.block_count() || data[8..12] != self.memory[..4] {
+                    return self.fail(STATUS_MIFARE_AUTH);
+                }
+                let sector = Self::sector_of(block);
+                let trailer = Self::trailer_of(sector) * 16;
+                let offset = if data[0] == 0x60 { 0 } else { 10 };
+                if data[2..8] == self.memory[trailer + offset..trailer + offset + 6] {
+                    self.authenticated = Some(sector);
+                    (STATUS_OK, Vec::new())
+                } else {
+                    self.fail(STATUS_MIFARE_AUTH)
+                }
+            }
+            0x30 if data.len() >= 2 => {
+                let block = data[1] as usize;
+                if block >= self.block_count() || self.authenticated != Some(Self::sector_of(block))
+                {
+                    return self.fail(STATUS_MIFARE_AUTH);
+                }
+                if self.unreadable_blocks.contains(&block) {
+                    return self.fail(STATUS_TIMEOUT);
+                }
+                let mut out = self.memory[block * 16..block * 16 + 16].to_vec();
+                // Ключ A из трейлера никогда не читается
+                if block == Self::trailer_of(Self::sector_of(block)) {
+                    out[..6].fill(0);
+                }
+                (STATUS_OK, out)
+            }
+            0xA0 if data.len() >= 18 => {
+                let block = data[1] as usize;
+                if block == 0
+                    || block >= self.block_count()
+                    || self.authenticated != Some(Self::sector_of(block))
+                {
+                    return self.fail(STATUS_MIFARE_AUTH);
+                }
+                self.memory[block * 16..block * 16 + 16].copy_from_slice(&data[2..18]);
+                (STATUS_OK, Vec::new())
+            }
+            _ => self.fail(STATUS_TIMEOUT),
+        }
+    }
+
+    /// После NAK карта уходит в HALT и отвечает только после повторной активации.
+    fn fail(&mut self, status: u8) -> TagReply {
+        self.halted = true;
+        self.left_field |= self.leaves_field_on_nak;
+        self.authenticated = None;
+        (status, Vec::new())
+    }
+}
+
+/// NTAG213/215/216: дамп постранично, включая UID, CC и конфигурацию.
+#[derive(Debug, Clone)]
+pub struct NtagTag {
+    pub memory: Vec<u8>,
+    pub model: NtagModel,
+    /// GET_VERSION получает NAK, как у Ultralight C.
+    pub lacks_get_version: bool,
+    /// После первого NAK метку уносят из поля – повторно она не активируется.
+    pub leaves_field_on_nak: bool,
+    halted: bool,
+    left_field: bool,
+}
+
+impl NtagTag {
+    pub fn from_bytes(memory: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
+        let model = [NtagModel::Ntag213, NtagModel::Ntag215, NtagModel::Ntag216]
+            .into_iter()
+            .find(|m| m.total_pages() as usize * 4 == memory.len())
+            .ok_or_else(|| format!("Unexpected NTAG dump size {}", memory.len()))?;
+        Ok(NtagTag {
+            memory,
+            model,
+            lacks_get_version: false,
+            leaves_field_on_nak: false,
+            halted: false,
+            left_field: false,
+        })
+    }
+
+    /// Пустая NTAG с заводским CC и пустым NDEF TLV.
+    pub fn blank(model: NtagModel, uid: [u8; 7]) -> Self {
+        let mut memory = vec![0u8; model.total_pages() as usize * 4];
+        memory[..3].copy_from_slice(&uid[..3]);
+        memory[3] = 0x88 ^ uid[0] ^ uid[1] ^ uid[2];
+        memory[4..8].copy_from_slice(&uid[3..]);
+        memory[8] = uid[3] ^ uid[4] ^ uid[5] ^ uid[6];
+        let size = match model {
+            NtagModel::Ntag213 => 0x12,
+            NtagModel::Ntag215 => 0x3E,
+            NtagModel::Ntag216 => 0x6D,
+        };
+        memory[12..16].copy_from_slice(&[0xE1, 0x10, size, 0x00]);
+        memory[16..19].copy_from_slice(&[0x03, 0x00, 0xFE]);
+        NtagTag {
+            memory,
+            model,
+            lacks_get_version: false,
+            leaves_field_on_nak: false,
+            halted: false,
+            left_field: false,
+        }
+    }
+
+    pub fn uid(&self) -> Vec<u8> {
+        let mut uid = self.memory[..3].to_vec();
+        uid.extend_from_slice(&self.memory[4..8]);
+        uid
+    }
+
+    pub fn version(&self) -> [u8; 8] {
+        let size = match self.model {
+            NtagModel::Ntag213 => 0x0F,
+            NtagModel::Ntag215 => 0x11,
+            NtagModel::Ntag216 => 0x13,
+        };
+        [0x00, 0x04, 0x04, 0x02, 0x01, 0x00, size, 0x03]
+    }
+
+    fn transceive(&mut self, data: &[u8]) -> TagReply {
+        if self.halted || data.is_empty() {
+            return (STATUS_TIMEOUT, Vec::new());
+        }
+        let pages = self.model.total_pages() as usize;
+        match data[0] {
+            0x60 if !self.lacks_get_version => (STATUS_OK, self.version().to_vec()),
+            0x30 if data.len() >= 2 && (data[1] as usize) < pages => {
+                // READ отдаёт 4 страницы и заворачивается на начало памяти
+                let out = (0..16)
+                    .map(|i| self.memory[((data[1] as usize) * 4 + i) % (pages * 4)])
+                    .collect();
+                (STATUS_OK, out)
+            }
+            0xA2 if data.len() >= 6 && (2..pages).contains(&(data[1] as usize)) => {
+                let page = data[1] as usize;
+                self.memory[page * 4..page * 4 + 4].copy_from_slice(&data[2..6]);
+                (STATUS_OK, Vec::new())
+            }
+            _ => {
+                self.halted = true;
+                self.left_field |= self.leaves_field_on_nak;
+                (STATUS_TIMEOUT, Vec::new())
+            }
+        }
+    }
+}
+
+/// Карта ISO14443-4 с приложением NDEF Type 4 – как DESFire с NDEF или телефон
+/// с HCE-приложением. APDU обрабатывает тот же `Type4Emulator`, что и режим
+/// карты PN532, только с записью.
+#[derive(Debug, Clone)]
+pub struct Type4Tag {
+    pub uid: Vec<u8>,
+    pub emulator: Type4Emulator,
+    halted: bool,
+}
+
+impl Type4Tag {
+    /// NDEF-файл размером `file_size` с сообщением `message`, MLe = 0x80.
+    pub fn new(uid: [u8; 7], message: &[u8], file_size: usize) -> Self {
+        let mut emulator = Type4Emulator::with_file_size(message, file_size);
+        emulator.max_read = 0x80;
+        emulator.writable = true;
+        Type4Tag {
+            uid: uid.to_vec(),
+            emulator,
+            halted: false,
+        }
+    }
+
+    /// TL T0 TA TB TC + исторический байт, FSCI = 5 (64 байта).
+    pub fn ats(&self) -> Vec<u8> {
+        vec![0x06, 0x75, 0x77, 0x81, 0x02, 0x80]
+    }
+
+    fn transceive(&mut self, apdu: &[u8]) -> TagReply {
+        if self.halted {
+            return (STATUS_TIMEOUT, Vec::new());
+        }
+        (STATUS_OK, self.emulator.respond(apdu))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum VirtualTag {
+    Classic(ClassicTag),
+    Ntag(NtagTag),
+    Type4(Type4Tag),
+}
+
+impl VirtualTag {
+    /// Тип метки определяется по размеру дампа.
+    pub fn from_dump_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let memory = std::fs::read(path)?;
+        match memory.len() {
+            320 | 1024 | 4096 => Ok(VirtualTag::Classic(ClassicTag::from_bytes(memory)?)),
+            _ => Ok(VirtualTag::Ntag(NtagTag::from_bytes(memory)?)),
+        }
+    }
+
+    pub fn uid(&self) -> Vec<u8> {
+        match self {
+            VirtualTag::Classic(tag) => tag.uid(),
+            VirtualTag::Ntag(tag) => tag.uid(),
+            VirtualTag::Type4(tag) => tag.uid.clone(),
+        }
+    }
+
+    pub fn sens_res(&self) -> u16 {
+        match self {
+            VirtualTag::Classic(tag) => tag.sens_res(),
+            VirtualTag::Ntag(_) => 0x0044,
+            // Как у телефона в режиме HCE
+            VirtualTag::Type4(_) => 0x0004,
+        }
+    }
+
+    pub fn sel_res(&self) -> u8 {
+        match self {
+            VirtualTag::Classic(tag) => tag.sel_res(),
+            VirtualTag::Ntag(_) => 0x00,
+            VirtualTag::Type4(_) => 0x20,
+        }
+    }
+
+    /// ATS есть только у карт ISO14443-4.
+    pub fn ats(&self) -> Option<Vec<u8>> {
+        match self {
+            VirtualTag::Type4(tag) => Some(tag.ats()),
+            _ => None,
+        }
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        match self {
+            VirtualTag::Classic(tag) => &tag.memory,
+            VirtualTag::Ntag(tag) => &tag.memory,
+            VirtualTag::Type4(tag) => &tag.emulator.ndef_file,
+        }
+    }
+
+    /// REQA/WUPA + anticollision: метка снова отвечает, аутентификация сброшена.
+    pub(crate) fn activate(&mut self) {
+        match self {
+            VirtualTag::Classic(tag) => {
+                tag.halted = false;
+                tag.authenticated = None;
+            }
+            VirtualTag::Ntag(tag) => tag.halted = false,
+            VirtualTag::Type4(tag) => {
+                tag.halted = false;
+                tag.emulator.reset();
+            }
+        }
+    }
+
+    /// Метку унесли из поля: на активацию она больше не отвечает.
+    pub(crate) fn left_field(&self) -> bool {
+        match self {
+            VirtualTag::Classic(tag) => tag.left_field,
+            VirtualTag::Ntag(tag) => tag.left_field,
+            VirtualTag::Type4(_) => false,
+        }
+    }
+
+    pub(crate) fn is_halted(&self) -> bool {
+        match self {
+            VirtualTag::Classic(tag) => tag.halted,
+            VirtualTag::Ntag(tag) => tag.halted,
+            VirtualTag::Type4(tag) => tag.halted,
+        }
+    }
+
+    /// HLTA: до следующей активации метка молчит.
+    pub(crate) fn halt(&mut self) {
+        match self {
+            VirtualTag::Classic(tag) => {
+                tag.halted = true;
+                tag.authenticated = None;
+            }
+            VirtualTag::Ntag(tag) => tag.halted = true,
+            VirtualTag::Type4(tag) => tag.halted = true,
+        }
+    }
+
+    /// InDataExchange: MIFARE-команды (аутентификация обрабатывается самим PN532)
+    /// или APDU для карт ISO14443-4 – T=CL остаётся на стороне PN532.
+    pub(crate) fn data_exchange(&mut self, data: &[u8]) -> TagReply {
+        match self {
+            VirtualTag::Classic(tag) => tag.transceive(data),
+            VirtualTag::Ntag(tag) => tag.transceive(data),
+            VirtualTag::Type4(tag) => tag.transceive(data),
+        }
+    }
+
+    /// InCommunicateThru: сырые кадры, у Classic без шифрования ответа не будет.
+    pub(crate) fn communicate_thru(&mut self, data: &[u8]) -> TagReply {
+        match self {
+            VirtualTag::Classic(tag) => tag.fail(STATUS_TIMEOUT),
+            VirtualTag::Ntag(tag) => tag.transceive(data),
+            VirtualTag::Type4(_) => (STATUS_TIMEOUT, Vec::new()),
+        }
+    }
+}
@@ -1,4 +1,19 @@
+pub mod apdu;
+pub mod autopoll;
+pub mod card;
 pub mod commands;
 pub mod constants;
 pub mod device;
+pub mod emulation;
+pub mod error;
+pub mod frame;
+pub mod keys;
+#[cfg(test)]
+pub mod mock;
+pub mod ntag;
+pub mod power;
+pub mod presence;
 pub mod response;
+pub mod rfconfig;
+pub mod target;
+pub mod transport;
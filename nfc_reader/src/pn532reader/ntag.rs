@@ -0,0 +1,333 @@
+use super::constants::*;
+use super::device::PN532;
+use super::error::Pn532Error;
+use tracing::debug;
+
+// Команды NTAG21x (NFC Forum Type 2)
+pub const NTAG_CMD_GET_VERSION: u8 = 0x60;
+pub const NTAG_CMD_WRITE: u8 = 0xA2;
+
+/// Первая страница пользовательской области; страницы 0-2 – UID и lock-байты, 3 – CC.
+pub const NTAG_USER_START_PAGE: u8 = 4;
+pub const NTAG_CC_PAGE: u8 = 3;
+pub const NTAG_PAGE_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NtagModel {
+    Ntag213,
+    Ntag215,
+    Ntag216,
+}
+
+impl NtagModel {
+    /// Определяет модель по ответу GET_VERSION: `00 04 04 02 01 00 <size> 03`.
+    pub fn from_version(version: &[u8; 8]) -> Option<Self> {
+        // vendor = NXP, product type = NTAG
+        if version[1] != 0x04 || version[2] != 0x04 {
+            return None;
+        }
+        match version[6] {
+            0x0F => Some(NtagModel::Ntag213),
+            0x11 => Some(NtagModel::Ntag215),
+            0x13 => Some(NtagModel::Ntag216),
+            _ => None,
+        }
+    }
+
+    pub fn total_pages(&self) -> u8 {
+        match self {
+            NtagModel::Ntag213 => 45,
+            NtagModel::Ntag215 => 135,
+            NtagModel::Ntag216 => 231,
+        }
+    }
+
+    /// Размер пользовательской памяти в байтах (страницы 4..=last user page).
+    pub fn user_bytes(&self) -> usize {
+        match self {
+            NtagModel::Ntag213 => 144,
+            NtagModel::Ntag215 => 504,
+            NtagModel::Ntag216 => 888,
+        }
+    }
+
+    pub fn last_user_page(&self) -> u8 {
+        NTAG_USER_START_PAGE + (self.user_bytes() / NTAG_PAGE_SIZE) as u8 - 1
+    }
+}
+
+/// Сколько байт данных адресуется однобайтовым номером страницы после 4-й.
+const NTAG_ADDRESSABLE_USER_BYTES: usize = (256 - NTAG_USER_START_PAGE as usize) * NTAG_PAGE_SIZE;
+
+/// Номер `index`-й страницы пользовательской области.
+fn user_page(index: usize) -> Result<u8, Pn532Error> {
+    u8::try_from(NTAG_USER_START_PAGE as usize + index)
+        .map_err(|_| Pn532Error::InvalidResponse("data area exceeds tag memory"))
+}
+
+/// Страницы 0-3 (UID, статические lock-байты, CC – OTP) и всё после
+/// пользовательской области (динамические lock-байты, CFG) пишутся только с `force`.
+pub fn is_protected_page(page: u8, model: Option<NtagModel>) -> bool {
+    if page < NTAG_USER_START_PAGE {
+        return true;
+    }
+    match model {
+        Some(model) => page > model.last_user_page(),
+        None => false,
+    }
+}
+
+/// Capability Container – страница 3 метки Type 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapabilityContainer {
+    pub magic: u8,
+    pub version: u8,
+    /// Размер области данных в байтах (байт 2 × 8).
+    pub data_area_size: usize,
+    pub access: u8,
+}
+
+impl CapabilityContainer {
+    pub fn parse(page: &[u8]) -> Result<Self, Pn532Error> {
+        if page.len() < NTAG_PAGE_SIZE {
+            return Err(Pn532Error::InvalidResponse(
+                "capability container too short",
+            ));
+        }
+        // E1 – метка отформатирована под NDEF
+        if page[0] != 0xE1 {
+            return Err(Pn532Error::NotNdefFormatted(page[0]));
+        }
+        Ok(CapabilityContainer {
+            magic: page[0],
+            version: page[1],
+            data_area_size: page[2] as usize * 8,
+            access: page[3],
+        })
+    }
+
+    /// Старшие 4 бита – доступ на чтение, младшие – на запись (0x0 – разрешено).
+    pub fn is_writable(&self) -> bool {
+        self.access & 0x0F == 0x00
+    }
+}
+
+impl PN532 {
+    /// GET_VERSION идёт через InCommunicateThru: для него InDataExchange не подходит,
+    /// так как PN532 знает только MIFARE-команды.
+    pub fn ntag_get_version(&mut self) -> Result<[u8; 8], Pn532Error> {
+        self.write_command(&[PN532_COMMAND_INCOMMUNICATETHRU, NTAG_CMD_GET_VERSION])?;
+        self.read_ack()?;
+        let response = self.read_response(PN532_COMMAND_INCOMMUNICATETHRU, 20)?;
+        let data = Self::check_status(&response)?;
+        if data.len() >= 8 {
+            let mut version = [0u8; 8];
+            version.copy_from_slice(&data[..8]);
+            Ok(version)
+        } else {
+            Err(Pn532Error::InvalidResponse("GET_VERSION reply too short"))
+        }
+    }
+
+    /// READ возвращает сразу 4 страницы (16 байт) начиная с `page`.
+    pub fn ntag_read_pages(&mut self, page: u8) -> Result<Vec<u8>, Pn532Error> {
+        self.read_block(page)
+    }
+
+    /// WRITE (0xA2) – одна страница (4 байта).
+    pub fn ntag_write_page(
+        &mut self,
+        page: u8,
+        data: &[u8; 4],
+        model: Option<NtagModel>,
+        force: bool,
+    ) -> Result<(), Pn532Error> {
+        if !force && is_protected_page(page, model) {
+            return Err(Pn532Error::Protected(page));
+        }
+        let mut command = vec![PN532_COMMAND_INDATAEXCHANGE, 0x01, NTAG_CMD_WRITE, page];
+        command.extend_from_slice(data);
+
+        self.write_command(&command)?;
+        self.read_ack()?;
+        let response = self.read_response(PN532_COMMAND_INDATAEXCHANGE, 10)?;
+        Self::check_status(&response)?;
+        Ok(())
+    }
+
+    pub fn ntag_read_cc(&mut self) -> Result<CapabilityContainer, Pn532Error> {
+        let pages = self.ntag_read_pages(NTAG_CC_PAGE)?;
+        CapabilityContainer::parse(&pages[..NTAG_PAGE_SIZE])
+    }
+
+    /// Записывает `data` в область данных с 4-й страницы и читает обратно.
+    /// Ёмкость берётся из CC (и модели, если она известна); без CC метка не
+    /// пишется вовсе – иначе большой payload затёр бы lock-байты и CFG.
+    pub fn ntag_write_user_data(
+        &mut self,
+        data: &[u8],
+        model: Option<NtagModel>,
+        force: bool,
+    ) -> Result<(), Pn532Error> {
+        let cc = self.ntag_read_cc()?;
+        if !cc.is_writable() && !force {
+            return Err(Pn532Error::Protected(NTAG_CC_PAGE));
+        }
+        let mut capacity = cc.data_area_size.min(NTAG_ADDRESSABLE_USER_BYTES);
+        if let Some(model) = model {
+            capacity = capacity.min(model.user_bytes());
+        }
+        if data.len() > capacity {
+            return Err(Pn532Error::PayloadTooLarge {
+                len: data.len(),
+                capacity,
+            });
+        }
+
+        let pages: Vec<[u8; 4]> = data
+            .chunks(NTAG_PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [0u8; 4];
+                page[..chunk.len()].copy_from_slice(chunk);
+                page
+            })
+            .collect();
+        for (i, page) in pages.iter().enumerate() {
+            let number = user_page(i)?;
+            self.ntag_write_page(number, page, model, force)?;
+            debug!(page = number, "page written");
+        }
+
+        // READ отдаёт по 4 страницы за раз
+        for (i, group) in pages.chunks(4).enumerate() {
+            let first = user_page(i * 4)?;
+            let read_back = self.ntag_read_pages(first)?;
+            for (n, page) in group.iter().enumerate() {
+                if read_back[n * NTAG_PAGE_SIZE..(n + 1) * NTAG_PAGE_SIZE] != page[..] {
+                    return Err(Pn532Error::VerificationFailed(first + n as u8));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Считывает область данных NDEF (с 4-й страницы) размером, указанным в CC.
+    /// Если модель известна, чтение не выходит за пользовательскую память –
+    /// иначе READ «заворачивается» на нулевую страницу.
+    pub fn ntag_read_user_data(&mut self, model: Option<NtagModel>) -> Result<Vec<u8>, Pn532Error> {
+        let cc = self.ntag_read_cc()?;
+        debug!(
+            version = format_args!("{:02X}", cc.version),
+            data_area = cc.data_area_size,
+            access = format_args!("{:02X}", cc.access),
+            "NTAG CC"
+        );
+        let mut size = cc.data_area_size;
+        if let Some(model) = model {
+            size = size.min(model.user_bytes());
+        }
+
+        let mut data = Vec::with_capacity(size + 16);
+        let mut page = NTAG_USER_START_PAGE;
+        while data.len() < size {
+            let chunk = self.ntag_read_pages(page)?;
+            data.extend_from_slice(&chunk);
+            page = page
+                .checked_add(4)
+                .ok_or(Pn532Error::InvalidResponse("data area exceeds tag memory"))?;
+        }
+        data.truncate(size);
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn532reader::card::CardType;
+    use crate::pn532reader::mock::{self, NtagTag, SimPn532, VirtualTag};
+
+    const UID: [u8; 7] = [0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66];
+
+    #[test]
+    fn detects_ntag215_and_reads_user_area() {
+        let mut tag = NtagTag::blank(NtagModel::Ntag215, UID);
+        tag.memory[16..22].copy_from_slice(&[0x03, 0x02, 0xAA, 0xBB, 0xFE, 0x00]);
+        let (mut pn532, _) = mock::connect(SimPn532::with_tag(VirtualTag::Ntag(tag)));
+
+        let target = pn532.read_passive_target().unwrap();
+        assert!(CardType::from_target(&target).is_type2());
+        assert_eq!(target.uid, UID);
+
+        let model = NtagModel::from_version(&pn532.ntag_get_version().unwrap());
+        assert_eq!(model, Some(NtagModel::Ntag215));
+
+        let data = pn532.ntag_read_user_data(model).unwrap();
+        assert_eq!(data.len(), 496);
+        assert_eq!(&data[..5], &[0x03, 0x02, 0xAA, 0xBB, 0xFE]);
+    }
+
+    #[test]
+    fn write_page_and_protected_pages() {
+        let tag = NtagTag::blank(NtagModel::Ntag213, UID);
+        let (mut pn532, _) = mock::connect(SimPn532::with_tag(VirtualTag::Ntag(tag)));
+        pn532.read_passive_target().unwrap();
+
+        let model = Some(NtagModel::Ntag213);
+        pn532
+            .ntag_write_page(4, &[1, 2, 3, 4], model, false)
+            .unwrap();
+        assert_eq!(&pn532.ntag_read_pages(4).unwrap()[..4], &[1, 2, 3, 4]);
+
+        assert!(pn532.ntag_write_page(3, &[0; 4], model, false).is_err());
+        assert!(pn532.ntag_write_page(41, &[0; 4], model, false).is_err());
+    }
+
+    #[test]
+    fn write_user_data_checks_capacity_and_verifies() {
+        let tag = NtagTag::blank(NtagModel::Ntag213, UID);
+        let (mut pn532, chip) = mock::connect(SimPn532::with_tag(VirtualTag::Ntag(tag)));
+        pn532.read_passive_target().unwrap();
+
+        let data: Vec<u8> = (0..=143).collect();
+        pn532
+            .ntag_write_user_data(&data, Some(NtagModel::Ntag213), false)
+            .unwrap();
+        assert_eq!(pn532.ntag_read_user_data(None).unwrap(), data);
+
+        // На байт больше области данных – ничего не пишется
+        let writes = chip.borrow().received.len();
+        assert!(matches!(
+            pn532.ntag_write_user_data(&[0; 145], None, false),
+            Err(Pn532Error::PayloadTooLarge {
+                len: 145,
+                capacity: 144
+            })
+        ));
+        assert_eq!(chip.borrow().received.len(), writes + 1);
+    }
+
+    #[test]
+    fn write_user_data_requires_cc() {
+        let mut tag = NtagTag::blank(NtagModel::Ntag216, UID);
+        // CC на 2 КБ, чего не адресовать однобайтовым номером страницы
+        tag.memory[12..16].copy_from_slice(&[0xE1, 0x10, 0xFF, 0x00]);
+        let (mut pn532, chip) = mock::connect(SimPn532::with_tag(VirtualTag::Ntag(tag)));
+        pn532.read_passive_target().unwrap();
+        assert!(matches!(
+            pn532.ntag_write_user_data(&[0; 1200], None, false),
+            Err(Pn532Error::PayloadTooLarge { capacity: 1008, .. })
+        ));
+
+        if let Some(VirtualTag::Ntag(tag)) = chip.borrow_mut().tag.as_mut() {
+            tag.memory[12..16].fill(0);
+        }
+        assert!(matches!(
+            pn532.ntag_write_user_data(&[1, 2, 3], None, false),
+            Err(Pn532Error::NotNdefFormatted(0x00))
+        ));
+        if let Some(VirtualTag::Ntag(tag)) = chip.borrow().tag.as_ref() {
+            assert_eq!(&tag.memory[16..20], &[0x03, 0x00, 0xFE, 0x00]);
+        };
+    }
+}
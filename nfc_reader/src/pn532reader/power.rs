@@ -0,0 +1,202 @@
+use crate::pn532reader::constants::*;
+use crate::pn532reader::device::PN532;
+use crate::pn532reader::error::Pn532Error;
+use gpiocdev::line::Value;
+use gpiocdev::Request;
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+/// RSTPDN держим в 0 дольше минимальных 100 мкс из даташита.
+const RESET_PULSE: Duration = Duration::from_millis(10);
+/// После отпускания RSTPDN чип загружается пару миллисекунд.
+const BOOT_DELAY: Duration = Duration::from_millis(20);
+
+/// WakeUpEnable для PowerDown: будить по любому из хост-интерфейсов
+/// (бит 7 – I2C, бит 5 – SPI, бит 4 – HSU).
+pub const WAKE_ON_HOST: u8 = 0xB0;
+
+/// Вывод RSTPDN PN532: 0 – сброс и Power Down, 1 – работа.
+pub trait ResetPin {
+    fn set_reset(&mut self, asserted: bool) -> Result<(), Pn532Error>;
+}
+
+/// RSTPDN на линии GPIO через gpiocdev.
+pub struct GpioResetPin {
+    request: Request,
+    offset: u32,
+}
+
+impl GpioResetPin {
+    /// `chip` – `/dev/gpiochip0` или `gpiochip0`. Линия сразу выставляется в 1.
+    pub fn new(chip: &str, line: u32) -> Result<Self, Pn532Error> {
+        let path = if chip.starts_with('/') {
+            chip.to_string()
+        } else {
+            format!("/dev/{}", chip)
+        };
+        let request = Request::builder()
+            .on_chip(path)
+            .with_consumer("nfc_reader")
+            .with_line(line)
+            .as_output(Value::Active)
+            .request()
+            .map_err(Pn532Error::transport)?;
+        Ok(GpioResetPin {
+            request,
+            offset: line,
+        })
+    }
+}
+
+impl ResetPin for GpioResetPin {
+    fn set_reset(&mut self, asserted: bool) -> Result<(), Pn532Error> {
+        let value = if asserted {
+            Value::Inactive
+        } else {
+            Value::Active
+        };
+        self.request
+            .set_value(self.offset, value)
+            .map_err(Pn532Error::transport)?;
+        Ok(())
+    }
+}
+
+/// Считает ошибки шины подряд: после `limit` штук чип пора сбрасывать.
+#[derive(Debug, Clone, Copy)]
+pub struct Watchdog {
+    limit: u32,
+    failures: u32,
+}
+
+impl Watchdog {
+    pub fn new(limit: u32) -> Self {
+        Watchdog {
+            limit: limit.max(1),
+            failures: 0,
+        }
+    }
+
+    pub fn success(&mut self) {
+        self.failures = 0;
+    }
+
+    /// `true` – набралось `limit` ошибок подряд; счётчик при этом обнуляется.
+    pub fn failure(&mut self) -> bool {
+        self.failures += 1;
+        if self.failures >= self.limit {
+            self.failures = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+}
+
+impl PN532 {
+    pub fn with_reset_pin(mut self, pin: Box<dyn ResetPin>) -> Self {
+        self.reset_pin = Some(pin);
+        self
+    }
+
+    /// Будит чип, проверяет прошивку, настраивает SAM и RF (`with_rf_config`).
+    /// Возвращает версию прошивки.
+    pub fn init(&mut self) -> Result<u32, Pn532Error> {
+        self.wake_up()?;
+        let version = self.get_firmware_version()?;
+        self.sam_configuration()?;
+        self.apply_rf_config()?;
+        Ok(version)
+    }
+
+    /// Восстанавливает зависший чип: импульс на RSTPDN, если вывод подключён,
+    /// затем `init`. Без RSTPDN остаётся только заново разбудить и настроить.
+    pub fn recover(&mut self) -> Result<u32, Pn532Error> {
+        if let Some(pin) = self.reset_pin.as_mut() {
+            warn!("resetting PN532 via RSTPDN");
+            pin.set_reset(true)?;
+            thread::sleep(RESET_PULSE);
+            pin.set_reset(false)?;
+            thread::sleep(BOOT_DELAY);
+        }
+        self.init()
+    }
+
+    /// PowerDown: чип гасит поле и почти не потребляет, пока его не разбудят
+    /// по одному из интерфейсов `wake_sources` (см. `WAKE_ON_HOST`).
+    pub fn power_down(&mut self, wake_sources: u8) -> Result<(), Pn532Error> {
+        self.write_command(&[PN532_COMMAND_POWERDOWN, wake_sources])?;
+        self.read_ack()?;
+        let response = self.read_response(PN532_COMMAND_POWERDOWN, 10)?;
+        Self::check_status(&response)?;
+        Ok(())
+    }
+
+    /// Выход из PowerDown: обращение по шине будит чип, после чего SAM
+    /// и RF настраиваются заново.
+    pub fn power_up(&mut self) -> Result<(), Pn532Error> {
+        self.wake_up()?;
+        self.sam_configuration()?;
+        self.apply_rf_config()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn532reader::mock::{self, MockResetPin, SimPn532};
+
+    #[test]
+    fn watchdog_counts_consecutive_failures() {
+        let mut watchdog = Watchdog::new(3);
+        assert!(!watchdog.failure());
+        assert!(!watchdog.failure());
+        watchdog.success();
+        assert!(!watchdog.failure());
+        assert!(!watchdog.failure());
+        assert!(watchdog.failure());
+        assert_eq!(watchdog.failures(), 0);
+    }
+
+    #[test]
+    fn power_down_until_woken() {
+        let (mut pn532, _) = mock::connect(SimPn532::new());
+        pn532.power_down(WAKE_ON_HOST).unwrap();
+        assert!(pn532.get_firmware_version().unwrap_err().is_bus_error());
+        pn532.power_up().unwrap();
+        assert_eq!(pn532.get_firmware_version().unwrap(), 0x320106);
+    }
+
+    #[test]
+    fn recover_resets_wedged_chip() {
+        let (pn532, chip) = mock::connect(SimPn532::new());
+        let mut pn532 = pn532.with_reset_pin(Box::new(MockResetPin::new(chip.clone())));
+        chip.borrow_mut().wedged = true;
+
+        let mut watchdog = Watchdog::new(2);
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            assert!(pn532.read_passive_target().unwrap_err().is_bus_error());
+            if watchdog.failure() {
+                pn532.recover().unwrap();
+                break;
+            }
+        }
+        assert_eq!(attempts, 2);
+        assert!(!chip.borrow().wedged);
+        assert_eq!(
+            chip.borrow().received.last().unwrap()[0],
+            PN532_COMMAND_SAMCONFIGURATION
+        );
+        assert!(matches!(
+            pn532.read_passive_target(),
+            Err(Pn532Error::NoTarget)
+        ));
+    }
+}
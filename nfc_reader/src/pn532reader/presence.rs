@@ -0,0 +1,234 @@
+use crate::pn532reader::constants::*;
+use crate::pn532reader::device::{Target, PN532};
+use crate::pn532reader::error::Pn532Error;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// Diagnose NumTst 0x06: отвечает ли активная карта ISO14443-4.
+const DIAGNOSE_CARD_PRESENCE: u8 = 0x06;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresenceEvent {
+    CardInserted(Target),
+    CardRemoved { uid: Vec<u8> },
+}
+
+/// Карта, которая сейчас считается лежащей на считывателе.
+#[derive(Debug, Clone)]
+struct Present {
+    target: Target,
+    /// Опросов подряд, в которых карта не ответила.
+    misses: u32,
+    /// Было ли `CardInserted`: вернувшаяся в cooldown карта лежит молча.
+    announced: bool,
+}
+
+/// Конечный автомат присутствия карты. Карта появляется сразу, а убранной
+/// считается только после `debounce` промахов подряд – один неудачный опрос
+/// не приводит к повторной обработке. Карта, вернувшаяся раньше `cooldown`
+/// после снятия, событий не порождает.
+pub struct PresenceTracker {
+    debounce: u32,
+    cooldown: Duration,
+    present: Option<Present>,
+    /// Когда карта с этим UID последний раз была убрана.
+    removed_at: HashMap<Vec<u8>, Instant>,
+    subscribers: Vec<Sender<PresenceEvent>>,
+}
+
+impl PresenceTracker {
+    pub fn new(debounce: u32, cooldown: Duration) -> Self {
+        PresenceTracker {
+            debounce: debounce.max(1),
+            cooldown,
+            present: None,
+            removed_at: HashMap::new(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Канал, в который будут приходить все последующие события.
+    pub fn subscribe(&mut self) -> Receiver<PresenceEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Карта, лежащая на считывателе, – её и нужно проверять вместо опроса поля.
+    pub fn current(&self) -> Option<&Target> {
+        self.present.as_ref().map(|present| &present.target)
+    }
+
+    /// Результат очередного опроса: `Some` – в поле карта, `None` – не ответила.
+    /// Возвращает события, которые заодно разосланы подписчикам.
+    pub fn observe(&mut self, seen: Option<Target>, now: Instant) -> Vec<PresenceEvent> {
+        let mut events = Vec::new();
+        match seen {
+            Some(target) => {
+                if let Some(present) = self.present.as_mut() {
+                    if present.target.uid == target.uid {
+                        present.misses = 0;
+                        return events;
+                    }
+                    // Карту подменили между опросами
+                    let present = self.present.take().unwrap();
+                    self.remove(present, now, &mut events);
+                }
+                let recent = self
+                    .removed_at
+                    .get(&target.uid)
+                    .is_some_and(|&at| now.duration_since(at) < self.cooldown);
+                if !recent {
+                    events.push(PresenceEvent::CardInserted(target.clone()));
+                }
+                self.present = Some(Present {
+                    target,
+                    misses: 0,
+                    announced: !recent,
+                });
+            }
+            None => {
+                if let Some(present) = self.present.as_mut() {
+                    present.misses += 1;
+                    if present.misses >= self.debounce {
+                        let present = self.present.take().unwrap();
+                        self.remove(present, now, &mut events);
+                    }
+                }
+            }
+        }
+        self.removed_at
+            .retain(|_, at| now.duration_since(*at) < self.cooldown);
+        self.publish(&events);
+        events
+    }
+
+    fn remove(&mut self, present: Present, now: Instant, events: &mut Vec<PresenceEvent>) {
+        if present.announced {
+            self.removed_at.insert(present.target.uid.clone(), now);
+            events.push(PresenceEvent::CardRemoved {
+                uid: present.target.uid,
+            });
+        }
+    }
+
+    /// Отключившиеся подписчики выбрасываются.
+    fn publish(&mut self, events: &[PresenceEvent]) {
+        for event in events {
+            self.subscribers
+                .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        }
+    }
+}
+
+impl PN532 {
+    /// Diagnose с тестом 0x06: `true`, если активная карта ISO14443-4 ответила.
+    pub fn diagnose_presence(&mut self) -> Result<bool, Pn532Error> {
+        self.write_command(&[PN532_COMMAND_DIAGNOSE, DIAGNOSE_CARD_PRESENCE])?;
+        self.read_ack()?;
+        let response = self.read_response(PN532_COMMAND_DIAGNOSE, 10)?;
+        match response.first() {
+            Some(0x00) => Ok(true),
+            Some(_) => Ok(false),
+            None => Err(Pn532Error::InvalidResponse("empty Diagnose response")),
+        }
+    }
+
+    /// Лежит ли карта всё ещё в поле. Карты ISO14443-4 проверяются через
+    /// Diagnose без потери сессии, остальные – повторной активацией по UID
+    /// (InListPassiveTarget будит и карту в HALT).
+    pub fn check_presence(&mut self, target: &Target) -> Result<bool, Pn532Error> {
+        if target.is_iso14443_4() {
+            return self.diagnose_presence();
+        }
+        match self.select_by_uid(&target.uid) {
+            Ok(_) => Ok(true),
+            Err(Pn532Error::NoTarget) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn532reader::mock::{self, ClassicTag, SimPn532, Type4Tag, VirtualTag};
+
+    fn card(uid: u8) -> Target {
+        let (mut pn532, _) = mock::connect(SimPn532::with_tag(VirtualTag::Classic(
+            ClassicTag::blank_1k([uid, 0, 0, 0]),
+        )));
+        pn532.read_passive_target().unwrap()
+    }
+
+    #[test]
+    fn debounces_removal_and_suppresses_quick_return() {
+        let mut tracker = PresenceTracker::new(3, Duration::from_secs(2));
+        let events = tracker.subscribe();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert_eq!(
+            tracker.observe(Some(card(1)), at(0)),
+            [PresenceEvent::CardInserted(card(1))]
+        );
+        // Два промаха – ещё не снятие
+        assert!(tracker.observe(None, at(50)).is_empty());
+        assert!(tracker.observe(None, at(100)).is_empty());
+        assert!(tracker.observe(Some(card(1)), at(150)).is_empty());
+        for ms in [200, 250] {
+            assert!(tracker.observe(None, at(ms)).is_empty());
+        }
+        assert_eq!(
+            tracker.observe(None, at(300)),
+            [PresenceEvent::CardRemoved {
+                uid: vec![1, 0, 0, 0]
+            }]
+        );
+        assert!(tracker.current().is_none());
+
+        // Та же карта в cooldown – молча, и её снятие тоже молча
+        assert!(tracker.observe(Some(card(1)), at(1000)).is_empty());
+        assert!(tracker.current().is_some());
+        assert_eq!(events.try_iter().count(), 2);
+
+        // Другая карта вытесняет молчаливую без CardRemoved
+        assert_eq!(
+            tracker.observe(Some(card(2)), at(1100)),
+            [PresenceEvent::CardInserted(card(2))]
+        );
+        for ms in [1150, 1200, 1250] {
+            tracker.observe(None, at(ms));
+        }
+        // После cooldown первая карта снова обрабатывается
+        assert_eq!(
+            tracker.observe(Some(card(1)), at(2500)),
+            [PresenceEvent::CardInserted(card(1))]
+        );
+        assert_eq!(events.try_iter().count(), 3);
+    }
+
+    #[test]
+    fn checks_presence_per_card_kind() {
+        let (mut pn532, chip) = mock::connect(SimPn532::with_tag(VirtualTag::Type4(
+            Type4Tag::new([0x08, 1, 2, 3, 4, 5, 6], &[], 256),
+        )));
+        let target = pn532.read_passive_target().unwrap();
+        assert!(pn532.check_presence(&target).unwrap());
+        assert_eq!(
+            chip.borrow().received.last().unwrap(),
+            &[PN532_COMMAND_DIAGNOSE, DIAGNOSE_CARD_PRESENCE]
+        );
+        chip.borrow_mut().tag = None;
+        assert!(!pn532.check_presence(&target).unwrap());
+
+        let classic = card(7);
+        chip.borrow_mut().tag = Some(VirtualTag::Classic(ClassicTag::blank_1k([7, 0, 0, 0])));
+        // Карта в HALT после InRelease всё равно находится по UID
+        pn532.in_release(0).unwrap();
+        assert!(pn532.check_presence(&classic).unwrap());
+        chip.borrow_mut().tag = Some(VirtualTag::Classic(ClassicTag::blank_1k([8, 0, 0, 0])));
+        assert!(!pn532.check_presence(&classic).unwrap());
+    }
+}
@@ -1,66 +1,76 @@
-use crate::pn532reader::constants::*;
+use crate::logging;
+use crate::metrics::METRICS;
 use crate::pn532reader::device::PN532;
-use embedded_hal::i2c::I2c;
+use crate::pn532reader::error::Pn532Error;
+use crate::pn532reader::frame::{self, Frame, FrameError, NACK_FRAME};
+use tracing::{trace, warn};
+
+/// Сколько раз просим PN532 повторить ответ (NACK) при ошибке контрольной суммы.
+const CHECKSUM_RETRIES: usize = 2;
 
 impl PN532 {
+    /// Читает ответ на `command` и возвращает данные после кода ответа.
     pub(crate) fn read_response(
         &mut self,
+        command: u8,
         max_length: usize,
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        if !self.wait_ready(1000)? {
-            return Err("Timeout waiting for response".into());
-        }
-        // Читаем весь ответ целиком
-        // +10 - резерв памяти
-        let mut buffer = vec![0u8; max_length + 10];
-        self.i2c.read(self.address, &mut buffer)?;
-        // 20.min(buffer.len()) - страхующий вызов от ситуаций, когда пакет пришёл частично или пустой.
-        // Если buffer.len() = 40, то срез будет buffer[0..20]
-        // Если buffer.len() = 8, то срез будет buffer[0..8] - безопасно
-        println!("RX raw data: {:02X?}", &buffer[..20.min(buffer.len())]);
-        // Пропускаем статусный байт
-        let buffer = &buffer[1..];
-        // Проверяем заголовок
-        if buffer[0] != 0x00 || buffer[1] != 0x00 || buffer[2] != 0xFF {
-            return Err("Invalid response header".into());
-        }
-        let len = buffer[3] as usize;
-        let lcs = buffer[4];
-        // Проверяем LCS
-        if ((len as u8).wrapping_add(lcs)) != 0x00 {
-            return Err("LCS checksum error".into());
-        }
-        // Проверяем, что у нас достаточно данных
-        // len = TFI + DATA
-        // PRE+START+LEN+LCS = 5 байтов
-        // DCS/POST = 2 байта
-        if buffer.len() < 5 + len + 2 {
-            return Err("Response too short".into());
-        }
-        // Проверяем TFI
-        if buffer[5] != PN532_PN532TOHOST {
-            return Err("Invalid TFI".into());
-        }
-        // Извлекаем данные (от позиции 6 до 6 + len - 1)
-        // len включает TFI, поэтому данные это с позиции 6 до 5 + len
-        let data_end = 5 + len;
-        let data = buffer[6..data_end].to_vec();
-        // Проверяем контрольную сумму
-        let mut checksum = 0u8;
-        for i in 5..data_end {
-            checksum = checksum.wrapping_add(buffer[i]);
-        }
-        // (TFI + DATA + DCS) % 256 == 0
-        checksum = (!checksum).wrapping_add(1);
-        if buffer[data_end] != checksum {
-            println!(
-                "Checksum error: expected {:02X}, got {:02X}",
-                checksum, buffer[data_end]
-            );
-        }
+    ) -> Result<Vec<u8>, Pn532Error> {
+        self.read_response_within(command, max_length, 1000)
+    }
 
-        println!("Response data: {:02X?}", data);
+    /// То же, но с явным временем ожидания готовности – для команд, которые
+    /// ждут внешнего события (например, появления инициатора).
+    pub(crate) fn read_response_within(
+        &mut self,
+        command: u8,
+        max_length: usize,
+        timeout_ms: u32,
+    ) -> Result<Vec<u8>, Pn532Error> {
+        let mut retries = 0;
+        loop {
+            if !self.wait_ready(timeout_ms)? {
+                return Err(Pn532Error::Timeout);
+            }
+            // Читаем весь ответ целиком
+            // +12 - резерв под заголовок расширенного фрейма, TFI и DCS/POST
+            let buffer = self.transport.read_frame(max_length + 12)?;
+            // 20.min(buffer.len()) - страхующий вызов от ситуаций, когда пакет пришёл частично или пустой.
+            // Если buffer.len() = 40, то срез будет buffer[0..20]
+            // Если buffer.len() = 8, то срез будет buffer[0..8] - безопасно
+            trace!(frame = %logging::bytes(&buffer[..20.min(buffer.len())]), "RX raw");
 
-        Ok(data)
+            match frame::parse_frame(&buffer) {
+                Ok(Frame::Data(data)) => {
+                    let data = frame::strip_response_code(command, &data)?;
+                    trace!(data = %logging::bytes(&data), "response");
+                    return Ok(data);
+                }
+                Ok(Frame::Ack) => return Err(FrameError::UnexpectedFrame("ACK").into()),
+                Ok(Frame::Nack) => return Err(FrameError::UnexpectedFrame("NACK").into()),
+                Err(e) if e.is_checksum() && retries < CHECKSUM_RETRIES => {
+                    METRICS.checksum_errors.inc();
+                    // NACK – PN532 отправит тот же ответ ещё раз
+                    warn!(error = %e, "requesting retransmission");
+                    retries += 1;
+                    self.transport.write_frame(&NACK_FRAME)?;
+                }
+                Err(e) => {
+                    if e.is_checksum() {
+                        METRICS.checksum_errors.inc();
+                    }
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+
+    /// Ответы InDataExchange/InCommunicateThru начинаются с байта статуса;
+    /// возвращает данные после него.
+    pub(crate) fn check_status(response: &[u8]) -> Result<&[u8], Pn532Error> {
+        match response.first() {
+            Some(0x00) => Ok(&response[1..]),
+            Some(&status) => Err(Pn532Error::CardStatus(status)),
+            None => Err(Pn532Error::InvalidResponse("empty response")),
+        }
     }
 }
@@ -0,0 +1,243 @@
+use crate::pn532reader::constants::*;
+use crate::pn532reader::device::PN532;
+use crate::pn532reader::error::Pn532Error;
+
+// CfgItem команды RFConfiguration (UM0701-02, 7.3.1)
+pub const CFG_RF_FIELD: u8 = 0x01;
+pub const CFG_TIMINGS: u8 = 0x02;
+pub const CFG_MAX_RTY_COM: u8 = 0x04;
+pub const CFG_MAX_RETRIES: u8 = 0x05;
+pub const CFG_ANALOG_TYPE_A: u8 = 0x0A;
+
+/// Повторять бесконечно (MxRtyATR, MxRtyPassiveActivation).
+pub const RETRY_FOREVER: u8 = 0xFF;
+
+/// Самый длинный таймаут RFConfiguration: код 0x10, 3,28 с.
+const MAX_TIMEOUT_CODE: u8 = 0x10;
+
+/// Таймаут в кодировке RFConfiguration: 0 – без таймаута, код n – 100 мкс · 2^(n-1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RfTimeout(pub u8);
+
+impl RfTimeout {
+    /// По умолчанию ATR_RES ждём 102,4 мс.
+    pub const ATR_RES_DEFAULT: RfTimeout = RfTimeout(0x0B);
+    /// По умолчанию ответ карты при InCommunicateThru и т.п. ждём 51,2 мс.
+    pub const RETRY_DEFAULT: RfTimeout = RfTimeout(0x0A);
+
+    /// Ближайший код не короче `micros`; больше 3,28 с не бывает.
+    pub fn from_micros(micros: u32) -> Self {
+        let code = (1..=MAX_TIMEOUT_CODE)
+            .find(|&code| 100u32 << (code - 1) >= micros)
+            .unwrap_or(MAX_TIMEOUT_CODE);
+        RfTimeout(code)
+    }
+
+    pub fn as_micros(&self) -> Option<u32> {
+        match self.0 {
+            0 => None,
+            code => Some(100u32 << (code.min(MAX_TIMEOUT_CODE) - 1)),
+        }
+    }
+}
+
+/// Регистры CIU для type A 106 кбит/с (CfgItem 0x0A), значения по умолчанию
+/// из даташита. Для станций за акрилом обычно поднимают `rf_cfg` (RxGain,
+/// биты 6-4) и проводимость передатчика `gs_n_on`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnalogTypeA {
+    pub rf_cfg: u8,
+    pub gs_n_on: u8,
+    pub cw_gs_p: u8,
+    pub mod_gs_p: u8,
+    pub demod_rf_on: u8,
+    pub rx_threshold: u8,
+    pub demod_rf_off: u8,
+    pub gs_n_off: u8,
+    pub mod_width: u8,
+    pub mif_nfc: u8,
+    pub tx_bit_phase: u8,
+}
+
+impl Default for AnalogTypeA {
+    fn default() -> Self {
+        AnalogTypeA::from_bytes([
+            0x59, 0xF4, 0x3F, 0x11, 0x4D, 0x85, 0x61, 0x6F, 0x26, 0x62, 0x87,
+        ])
+    }
+}
+
+impl AnalogTypeA {
+    pub fn from_bytes(bytes: [u8; 11]) -> Self {
+        AnalogTypeA {
+            rf_cfg: bytes[0],
+            gs_n_on: bytes[1],
+            cw_gs_p: bytes[2],
+            mod_gs_p: bytes[3],
+            demod_rf_on: bytes[4],
+            rx_threshold: bytes[5],
+            demod_rf_off: bytes[6],
+            gs_n_off: bytes[7],
+            mod_width: bytes[8],
+            mif_nfc: bytes[9],
+            tx_bit_phase: bytes[10],
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 11] {
+        [
+            self.rf_cfg,
+            self.gs_n_on,
+            self.cw_gs_p,
+            self.mod_gs_p,
+            self.demod_rf_on,
+            self.rx_threshold,
+            self.demod_rf_off,
+            self.gs_n_off,
+            self.mod_width,
+            self.mif_nfc,
+            self.tx_bit_phase,
+        ]
+    }
+
+    /// Усиление приёмника 0..=7 (18..48 дБ), биты 6-4 CIU_RFCfg.
+    pub fn with_rx_gain(mut self, gain: u8) -> Self {
+        self.rf_cfg = (self.rf_cfg & !0x70) | ((gain.min(7)) << 4);
+        self
+    }
+}
+
+/// Что из RFConfiguration менять; `None` – оставить значение чипа.
+/// Применяется в `init`, после сброса и выхода из PowerDown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RfConfig {
+    /// MxRtyPassiveActivation: сколько раз InListPassiveTarget ищет карту,
+    /// 0xFF – пока не найдёт.
+    pub passive_activation_retries: Option<u8>,
+    /// MxRtyATR: повторы ATR_REQ при активации DEP.
+    pub atr_retries: Option<u8>,
+    /// MaxRtyCOM: повторы InDataExchange/InCommunicateThru после таймаута карты.
+    pub com_retries: Option<u8>,
+    pub atr_res_timeout: Option<RfTimeout>,
+    /// Таймаут ответа карты при обмене.
+    pub retry_timeout: Option<RfTimeout>,
+    pub analog_type_a: Option<AnalogTypeA>,
+}
+
+impl PN532 {
+    pub fn with_rf_config(mut self, config: RfConfig) -> Self {
+        self.rf_config = Some(config);
+        self
+    }
+
+    /// RFConfiguration с произвольным CfgItem.
+    pub fn rf_configuration(&mut self, item: u8, data: &[u8]) -> Result<(), Pn532Error> {
+        let mut command = vec![PN532_COMMAND_RFCONFIGURATION, item];
+        command.extend_from_slice(data);
+        self.write_command(&command)?;
+        self.read_ack()?;
+        self.read_response(PN532_COMMAND_RFCONFIGURATION, 10)?;
+        Ok(())
+    }
+
+    /// Включает или гасит поле; `auto_rfca` – не включать поле, пока рядом
+    /// работает чужое.
+    pub fn set_rf_field(&mut self, on: bool, auto_rfca: bool) -> Result<(), Pn532Error> {
+        self.rf_configuration(CFG_RF_FIELD, &[(auto_rfca as u8) << 1 | on as u8])
+    }
+
+    pub fn set_timings(&mut self, atr_res: RfTimeout, retry: RfTimeout) -> Result<(), Pn532Error> {
+        self.rf_configuration(CFG_TIMINGS, &[0x00, atr_res.0, retry.0])
+    }
+
+    pub fn set_com_retries(&mut self, retries: u8) -> Result<(), Pn532Error> {
+        self.rf_configuration(CFG_MAX_RTY_COM, &[retries])
+    }
+
+    pub fn set_max_retries(
+        &mut self,
+        atr: u8,
+        psl: u8,
+        passive_activation: u8,
+    ) -> Result<(), Pn532Error> {
+        self.rf_configuration(CFG_MAX_RETRIES, &[atr, psl, passive_activation])
+    }
+
+    pub fn set_analog_type_a(&mut self, analog: &AnalogTypeA) -> Result<(), Pn532Error> {
+        self.rf_configuration(CFG_ANALOG_TYPE_A, &analog.to_bytes())
+    }
+
+    /// Отправляет заданные в `with_rf_config` настройки. Незаданные поля
+    /// CfgItem, которые идут одной командой, берутся по умолчанию из даташита.
+    pub(crate) fn apply_rf_config(&mut self) -> Result<(), Pn532Error> {
+        let Some(config) = self.rf_config else {
+            return Ok(());
+        };
+        if config.atr_res_timeout.is_some() || config.retry_timeout.is_some() {
+            self.set_timings(
+                config.atr_res_timeout.unwrap_or(RfTimeout::ATR_RES_DEFAULT),
+                config.retry_timeout.unwrap_or(RfTimeout::RETRY_DEFAULT),
+            )?;
+        }
+        if let Some(retries) = config.com_retries {
+            self.set_com_retries(retries)?;
+        }
+        if config.atr_retries.is_some() || config.passive_activation_retries.is_some() {
+            self.set_max_retries(
+                config.atr_retries.unwrap_or(RETRY_FOREVER),
+                0x01,
+                config.passive_activation_retries.unwrap_or(RETRY_FOREVER),
+            )?;
+        }
+        if let Some(analog) = config.analog_type_a {
+            self.set_analog_type_a(&analog)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn532reader::mock::{self, SimPn532};
+
+    #[test]
+    fn timeout_codes() {
+        assert_eq!(RfTimeout::from_micros(0), RfTimeout(0x01));
+        assert_eq!(RfTimeout::from_micros(50_000), RfTimeout::RETRY_DEFAULT);
+        assert_eq!(RfTimeout::from_micros(51_201), RfTimeout(0x0B));
+        assert_eq!(RfTimeout::from_micros(10_000_000), RfTimeout(0x10));
+        assert_eq!(RfTimeout::ATR_RES_DEFAULT.as_micros(), Some(102_400));
+        assert_eq!(RfTimeout(0).as_micros(), None);
+        assert_eq!(AnalogTypeA::default().with_rx_gain(7).rf_cfg, 0x79);
+    }
+
+    #[test]
+    fn rf_config_is_applied_on_init_and_after_power_down() {
+        let (pn532, chip) = mock::connect(SimPn532::new());
+        let mut pn532 = pn532.with_rf_config(RfConfig {
+            passive_activation_retries: Some(0x10),
+            retry_timeout: Some(RfTimeout::from_micros(100_000)),
+            analog_type_a: Some(AnalogTypeA::default().with_rx_gain(7)),
+            ..RfConfig::default()
+        });
+        pn532.init().unwrap();
+        {
+            let chip = chip.borrow();
+            assert_eq!(chip.rf_settings[&CFG_TIMINGS], [0x00, 0x0B, 0x0B]);
+            assert_eq!(chip.rf_settings[&CFG_MAX_RETRIES], [0xFF, 0x01, 0x10]);
+            assert_eq!(chip.rf_settings[&CFG_ANALOG_TYPE_A][0], 0x79);
+            assert!(!chip.rf_settings.contains_key(&CFG_MAX_RTY_COM));
+        }
+
+        chip.borrow_mut().reset();
+        assert!(chip.borrow().rf_settings.is_empty());
+        pn532.power_up().unwrap();
+        assert_eq!(chip.borrow().rf_settings.len(), 3);
+
+        // Неверная длина данных – синтаксическая ошибка PN532
+        assert!(pn532.rf_configuration(CFG_MAX_RETRIES, &[0x01]).is_err());
+        pn532.set_rf_field(false, false).unwrap();
+        assert_eq!(chip.borrow().rf_settings[&CFG_RF_FIELD], [0x00]);
+    }
+}
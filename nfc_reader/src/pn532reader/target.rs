@@ -0,0 +1,320 @@
+use crate::pn532reader::error::Pn532Error;
+
+/// Тип модуляции и скорость для InListPassiveTarget (байт BrTy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modulation {
+    /// 106 kbps type A (MIFARE, NTAG, DESFire...)
+    Iso14443A,
+    /// 212 kbps FeliCa
+    Felica212,
+    /// 424 kbps FeliCa
+    Felica424,
+    /// 106 kbps type B
+    Iso14443B,
+    /// 106 kbps Innovision Jewel/Topaz
+    Jewel,
+}
+
+impl Modulation {
+    pub fn brty(&self) -> u8 {
+        match self {
+            Modulation::Iso14443A => 0x00,
+            Modulation::Felica212 => 0x01,
+            Modulation::Felica424 => 0x02,
+            Modulation::Iso14443B => 0x03,
+            Modulation::Jewel => 0x04,
+        }
+    }
+
+    /// InitiatorData для InListPassiveTarget.
+    pub(crate) fn initiator_data(&self) -> &'static [u8] {
+        match self {
+            // POLLING: system code FFFF (любая), request code 01 (вернуть system code), 1 слот
+            Modulation::Felica212 | Modulation::Felica424 => &[0x00, 0xFF, 0xFF, 0x01, 0x00],
+            // AFI = 0 – все приложения
+            Modulation::Iso14443B => &[0x00],
+            Modulation::Iso14443A | Modulation::Jewel => &[],
+        }
+    }
+
+    /// PN532 за раз находит не больше двух целей, а для Jewel – только одну.
+    pub fn max_targets(&self) -> u8 {
+        match self {
+            Modulation::Jewel => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// Цель, найденная InListPassiveTarget.
+///
+/// `uid` – NFCID1 для type A, NFCID2 для FeliCa, PUPI для type B, JEWELID для Jewel.
+/// SENS_RES/SEL_RES есть только у type A (у Jewel – только SENS_RES), у остальных нули.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub tg: u8,
+    pub modulation: Modulation,
+    pub sens_res: u16,
+    pub sel_res: u8,
+    pub uid: Vec<u8>,
+    /// ATS карт ISO14443-4 (SEL_RES бит 5), вместе с байтом длины TL.
+    pub ats: Option<Vec<u8>>,
+    /// Ответ цели целиком: POL_RES для FeliCa, ATQB + ATTRIB_RES для type B.
+    pub target_data: Vec<u8>,
+}
+
+impl Target {
+    /// Карта поддерживает ISO14443-4 (APDU через InDataExchange).
+    pub fn is_iso14443_4(&self) -> bool {
+        match self.modulation {
+            Modulation::Iso14443A => self.sel_res & 0x20 != 0,
+            Modulation::Iso14443B => true,
+            _ => false,
+        }
+    }
+}
+
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], Pn532Error> {
+    let chunk = data
+        .get(*pos..*pos + len)
+        .ok_or(Pn532Error::InvalidResponse("target data too short"))?;
+    *pos += len;
+    Ok(chunk)
+}
+
+/// Разбирает ответ InListPassiveTarget (после кода ответа): `NbTg` и данные целей.
+/// Формат данных цели зависит от модуляции (UM0701-02, 7.3.5).
+pub fn parse_target_list(modulation: Modulation, data: &[u8]) -> Result<Vec<Target>, Pn532Error> {
+    let nb_targets = *data
+        .first()
+        .ok_or(Pn532Error::InvalidResponse("empty target list"))?;
+    let mut pos = 1;
+    let mut targets = Vec::with_capacity(nb_targets as usize);
+    for _ in 0..nb_targets {
+        targets.push(parse_target(modulation, data, &mut pos)?);
+    }
+    Ok(targets)
+}
+
+/// Данные одной цели, начиная с `Tg`; `pos` сдвигается за её конец.
+fn parse_target(
+    modulation: Modulation,
+    data: &[u8],
+    pos: &mut usize,
+) -> Result<Target, Pn532Error> {
+    let tg = take(data, pos, 1)?[0];
+    let start = *pos;
+    let mut target = Target {
+        tg,
+        modulation,
+        sens_res: 0,
+        sel_res: 0,
+        uid: Vec::new(),
+        ats: None,
+        target_data: Vec::new(),
+    };
+    match modulation {
+        Modulation::Iso14443A => {
+            // SENS_RES(2) SEL_RES NFCIDLength NFCID1 [ATS]
+            let header = take(data, pos, 4)?;
+            target.sens_res = u16::from_be_bytes([header[0], header[1]]);
+            target.sel_res = header[2];
+            target.uid = take(data, pos, header[3] as usize)?.to_vec();
+            if target.sel_res & 0x20 != 0 {
+                // TL – длина ATS, включая сам байт TL
+                let tl = *data
+                    .get(*pos)
+                    .ok_or(Pn532Error::InvalidResponse("ATS missing"))?
+                    as usize;
+                target.ats = Some(take(data, pos, tl.max(1))?.to_vec());
+            }
+        }
+        Modulation::Felica212 | Modulation::Felica424 => {
+            // POL_RES length (включая себя), 01, NFCID2t(8), Pad(8), [SYST_CODE(2)]
+            let len = take(data, pos, 1)?[0] as usize;
+            if len < 18 {
+                return Err(Pn532Error::InvalidResponse("FeliCa POL_RES too short"));
+            }
+            let pol_res = take(data, pos, len - 1)?;
+            target.uid = pol_res[1..9].to_vec();
+        }
+        Modulation::Iso14443B => {
+            // ATQB(12): 50 PUPI(4) AppData(4) ProtInfo(3), затем ATTRIB_RES length + ATTRIB_RES
+            let atqb = take(data, pos, 12)?;
+            target.uid = atqb[1..5].to_vec();
+            let len = take(data, pos, 1)?[0] as usize;
+            take(data, pos, len)?;
+        }
+        Modulation::Jewel => {
+            // SENS_RES(2) JEWELID(4)
+            let header = take(data, pos, 2)?;
+            target.sens_res = u16::from_be_bytes([header[0], header[1]]);
+            target.uid = take(data, pos, 4)?.to_vec();
+        }
+    }
+    target.target_data = data[start..*pos].to_vec();
+    Ok(target)
+}
+
+/// Тип цели для InAutoPoll (UM0701-02, 7.3.13). DEP-цели не поддерживаются.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollType {
+    /// Любая карта type A: MIFARE, NTAG, ISO14443-4A
+    GenericPassive106,
+    GenericPassive212,
+    GenericPassive424,
+    Iso14443B,
+    Jewel,
+    Mifare,
+    Felica212,
+    Felica424,
+    Iso14443_4A,
+    Iso14443_4B,
+}
+
+impl PollType {
+    pub fn code(&self) -> u8 {
+        match self {
+            PollType::GenericPassive106 => 0x00,
+            PollType::GenericPassive212 => 0x01,
+            PollType::GenericPassive424 => 0x02,
+            PollType::Iso14443B => 0x03,
+            PollType::Jewel => 0x04,
+            PollType::Mifare => 0x10,
+            PollType::Felica212 => 0x11,
+            PollType::Felica424 => 0x12,
+            PollType::Iso14443_4A => 0x20,
+            PollType::Iso14443_4B => 0x23,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        [
+            PollType::GenericPassive106,
+            PollType::GenericPassive212,
+            PollType::GenericPassive424,
+            PollType::Iso14443B,
+            PollType::Jewel,
+            PollType::Mifare,
+            PollType::Felica212,
+            PollType::Felica424,
+            PollType::Iso14443_4A,
+            PollType::Iso14443_4B,
+        ]
+        .into_iter()
+        .find(|t| t.code() == code)
+    }
+
+    /// В каком формате PN532 отдаёт данные цели этого типа.
+    pub fn modulation(&self) -> Modulation {
+        match self {
+            PollType::GenericPassive106 | PollType::Mifare | PollType::Iso14443_4A => {
+                Modulation::Iso14443A
+            }
+            PollType::GenericPassive212 | PollType::Felica212 => Modulation::Felica212,
+            PollType::GenericPassive424 | PollType::Felica424 => Modulation::Felica424,
+            PollType::Iso14443B | PollType::Iso14443_4B => Modulation::Iso14443B,
+            PollType::Jewel => Modulation::Jewel,
+        }
+    }
+}
+
+/// Разбирает ответ InAutoPoll: `NbTg`, затем для каждой цели тип, длина и
+/// данные в формате InListPassiveTarget (начиная с `Tg`).
+pub fn parse_auto_poll(data: &[u8]) -> Result<Vec<(PollType, Target)>, Pn532Error> {
+    let nb_targets = *data
+        .first()
+        .ok_or(Pn532Error::InvalidResponse("empty InAutoPoll response"))?;
+    let mut pos = 1;
+    let mut targets = Vec::with_capacity(nb_targets as usize);
+    for _ in 0..nb_targets {
+        let header = take(data, &mut pos, 2)?;
+        let poll_type = PollType::from_code(header[0]).ok_or(Pn532Error::InvalidResponse(
+            "unsupported InAutoPoll target type",
+        ))?;
+        let target_data = take(data, &mut pos, header[1] as usize)?;
+        let target = parse_target(poll_type.modulation(), target_data, &mut 0)?;
+        targets.push((poll_type, target));
+    }
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_two_type_a_targets_with_ats() {
+        let data = [
+            0x02, // NbTg
+            0x01, 0x00, 0x04, 0x08, 0x04, 0xDE, 0xAD, 0xBE, 0xEF, // MIFARE Classic 1K
+            0x02, 0x03, 0x44, 0x20, 0x07, 0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, // DESFire
+            0x06, 0x75, 0x77, 0x81, 0x02, 0x80, // ATS
+        ];
+        let targets = parse_target_list(Modulation::Iso14443A, &data).unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].uid, [0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(targets[0].ats, None);
+        assert!(!targets[0].is_iso14443_4());
+        assert_eq!(targets[1].tg, 2);
+        assert_eq!(targets[1].sens_res, 0x0344);
+        assert_eq!(
+            targets[1].ats.as_deref(),
+            Some(&[0x06, 0x75, 0x77, 0x81, 0x02, 0x80][..])
+        );
+        assert!(targets[1].is_iso14443_4());
+
+        assert!(parse_target_list(Modulation::Iso14443A, &data[..20]).is_err());
+    }
+
+    #[test]
+    fn parses_felica_b_and_jewel() {
+        let mut felica = vec![0x01, 0x01, 0x14, 0x01];
+        felica.extend_from_slice(&[0x01, 0x2E, 0x3D, 0x4C, 0x5B, 0x6A, 0x79, 0x88]);
+        felica.extend_from_slice(&[0x00; 8]);
+        felica.extend_from_slice(&[0x88, 0xB4]);
+        let targets = parse_target_list(Modulation::Felica212, &felica).unwrap();
+        assert_eq!(
+            targets[0].uid,
+            [0x01, 0x2E, 0x3D, 0x4C, 0x5B, 0x6A, 0x79, 0x88]
+        );
+        assert_eq!(targets[0].target_data.len(), 20);
+
+        let b = [
+            0x01, 0x01, 0x50, 0x12, 0x34, 0x56, 0x78, 0x00, 0x00, 0x00, 0x00, 0x00, 0x71, 0x71,
+            0x01, 0x00,
+        ];
+        let targets = parse_target_list(Modulation::Iso14443B, &b).unwrap();
+        assert_eq!(targets[0].uid, [0x12, 0x34, 0x56, 0x78]);
+        assert!(targets[0].is_iso14443_4());
+
+        let jewel = [0x01, 0x01, 0x0C, 0x00, 0xA1, 0xB2, 0xC3, 0xD4];
+        let targets = parse_target_list(Modulation::Jewel, &jewel).unwrap();
+        assert_eq!(targets[0].sens_res, 0x0C00);
+        assert_eq!(targets[0].uid, [0xA1, 0xB2, 0xC3, 0xD4]);
+
+        assert!(parse_target_list(Modulation::Iso14443A, &[0x00])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn parses_auto_poll_response() {
+        let data = [
+            0x02, // NbTg
+            0x10, 0x09, 0x01, 0x00, 0x04, 0x08, 0x04, 0xDE, 0xAD, 0xBE, 0xEF, // MIFARE 1K
+            0x04, 0x07, 0x01, 0x0C, 0x00, 0xA1, 0xB2, 0xC3, 0xD4, // Jewel
+        ];
+        let targets = parse_auto_poll(&data).unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].0, PollType::Mifare);
+        assert_eq!(targets[0].1.uid, [0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(targets[0].1.sel_res, 0x08);
+        assert_eq!(targets[1].1.modulation, Modulation::Jewel);
+        assert_eq!(targets[1].1.uid, [0xA1, 0xB2, 0xC3, 0xD4]);
+
+        assert!(parse_auto_poll(&[0x00]).unwrap().is_empty());
+        assert!(parse_auto_poll(&[0x01, 0x40, 0x01, 0x01]).is_err());
+        assert!(parse_auto_poll(&data[..15]).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use super::Transport;
+use crate::pn532reader::error::Pn532Error;
+use serialport::{ClearBuffer, SerialPort, TTYPort};
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+/// Длинная преамбула 0x55 + нули будит PN532 из Power Down / LowVbat.
+const HSU_WAKEUP: [u8; 16] = [
+    0x55, 0x55, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// High Speed UART. В отличие от I2C/SPI статусного байта нет: готовность –
+/// это просто появление байт в приёмном буфере, а фрейм читается по его LEN.
+pub struct HsuTransport {
+    port: TTYPort,
+}
+
+impl HsuTransport {
+    pub fn new(device: &str, baud_rate: u32) -> Result<Self, Pn532Error> {
+        let port = serialport::new(device, baud_rate)
+            .timeout(Duration::from_millis(100))
+            .open_native()
+            .map_err(Pn532Error::transport)?;
+        Ok(HsuTransport { port })
+    }
+}
+
+impl Transport for HsuTransport {
+    fn wake_up(&mut self) -> Result<(), Pn532Error> {
+        self.port.write_all(&HSU_WAKEUP)?;
+        self.port.flush()?;
+        // Чипу нужно немного времени, чтобы проснуться до первой команды
+        std::thread::sleep(Duration::from_millis(10));
+        self.port
+            .clear(ClearBuffer::Input)
+            .map_err(Pn532Error::transport)?;
+        Ok(())
+    }
+
+    fn wait_ready(&mut self, timeout_ms: u32) -> Result<bool, Pn532Error> {
+        let start = Instant::now();
+
+        loop {
+            if self.port.bytes_to_read().map_err(Pn532Error::transport)? > 0 {
+                return Ok(true);
+            }
+
+            if start.elapsed().as_millis() > timeout_ms as u128 {
+                return Ok(false);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), Pn532Error> {
+        // Хвосты прошлых ответов сбили бы разбор следующего фрейма
+        self.port
+            .clear(ClearBuffer::Input)
+            .map_err(Pn532Error::transport)?;
+        self.port.write_all(frame)?;
+        self.port.flush()?;
+        Ok(())
+    }
+
+    fn read_frame(&mut self, len: usize) -> Result<Vec<u8>, Pn532Error> {
+        read_stream_frame(&mut self.port, len)
+    }
+}
+
+fn read_exact(port: &mut impl Read, len: usize) -> Result<Vec<u8>, Pn532Error> {
+    let mut buffer = vec![0u8; len];
+    port.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Читает из потока ровно один фрейм по его LEN. Битая длина или фрейм
+/// длиннее `len` – ошибка: дочитывать по такой длине или обрезать фрейм
+/// значит отдать разбору мусор вместо ответа.
+fn read_stream_frame(port: &mut impl Read, len: usize) -> Result<Vec<u8>, Pn532Error> {
+    // PRE SC1 SC2 LEN LCS
+    let mut frame = read_exact(port, 5)?;
+    if frame[..3] != [0x00, 0x00, 0xFF] {
+        return Err(Pn532Error::InvalidResponse("no frame start code"));
+    }
+    let rest = match (frame[3], frame[4]) {
+        // ACK (00 FF) и NACK (FF 00) – остаётся только постамбула
+        (0x00, 0xFF) | (0xFF, 0x00) => 1,
+        // Расширенный фрейм: LENM LENL LCS, затем TFI+DATA, DCS, POST
+        (0xFF, 0xFF) => {
+            let ext = read_exact(port, 3)?;
+            if ext[0].wrapping_add(ext[1]).wrapping_add(ext[2]) != 0 {
+                return Err(Pn532Error::InvalidResponse(
+                    "extended frame length is corrupt",
+                ));
+            }
+            let ext_len = u16::from_be_bytes([ext[0], ext[1]]) as usize;
+            frame.extend_from_slice(&ext);
+            ext_len + 2
+        }
+        (data_len, lcs) if data_len.wrapping_add(lcs) != 0 => {
+            return Err(Pn532Error::InvalidResponse("frame length is corrupt"));
+        }
+        (0, _) => return Err(Pn532Error::InvalidResponse("frame has no TFI")),
+        (data_len, _) => data_len as usize + 2,
+    };
+    if frame.len() + rest > len {
+        return Err(Pn532Error::InvalidResponse("frame longer than expected"));
+    }
+    frame.extend(read_exact(port, rest)?);
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn532reader::frame::{self, Frame};
+
+    #[test]
+    fn reads_one_frame_from_stream() {
+        let response = frame::build_frame(0xD5, &[0x03, 0x32, 0x01, 0x06, 0x07]);
+        let ack = [0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00];
+        let mut stream: Vec<u8> = ack.to_vec();
+        stream.extend_from_slice(&response);
+        stream.extend_from_slice(&[0x55; 4]);
+        let mut stream = &stream[..];
+
+        assert_eq!(read_stream_frame(&mut stream, 6).unwrap(), ack);
+        assert_eq!(read_stream_frame(&mut stream, 64).unwrap(), response);
+        // Следующий фрейм не тронут
+        assert_eq!(stream, [0x55; 4]);
+
+        let long = frame::build_frame(0xD5, &[0xAB; 300]);
+        let read = read_stream_frame(&mut &long[..], 400).unwrap();
+        assert!(matches!(frame::parse_frame(&read), Ok(Frame::Data(data)) if data.len() == 300));
+    }
+
+    #[test]
+    fn rejects_corrupt_and_oversized_frames() {
+        let response = frame::build_frame(0xD5, &[0x03, 0x32, 0x01, 0x06, 0x07]);
+        // Ответ на команду вместо ACK не обрезается до шести байт
+        assert!(matches!(
+            read_stream_frame(&mut &response[..], 6),
+            Err(Pn532Error::InvalidResponse("frame longer than expected"))
+        ));
+
+        let mut corrupt = response.clone();
+        corrupt[3] = 0x40;
+        assert!(matches!(
+            read_stream_frame(&mut &corrupt[..], 64),
+            Err(Pn532Error::InvalidResponse("frame length is corrupt"))
+        ));
+        assert!(matches!(
+            read_stream_frame(
+                &mut &[0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x00][..],
+                64
+            ),
+            Err(Pn532Error::InvalidResponse(
+                "extended frame length is corrupt"
+            ))
+        ));
+        assert!(matches!(
+            read_stream_frame(&mut &[0x00, 0x55, 0xFF, 0x00, 0xFF, 0x00][..], 6),
+            Err(Pn532Error::InvalidResponse("no frame start code"))
+        ));
+        // Фрейм оборвался на середине
+        assert!(matches!(
+            read_stream_frame(&mut &response[..8], 64),
+            Err(Pn532Error::Transport(_))
+        ));
+    }
+}
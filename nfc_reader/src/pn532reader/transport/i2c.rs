@@ -0,0 +1,67 @@
+use super::Transport;
+use crate::pn532reader::error::Pn532Error;
+use embedded_hal::i2c::I2c;
+use linux_embedded_hal::I2cdev;
+use tracing::debug;
+
+pub struct I2cTransport {
+    i2c: I2cdev,
+    address: u8,
+}
+
+impl I2cTransport {
+    pub fn new(device: &str, address: u8) -> Result<Self, Pn532Error> {
+        let i2c = I2cdev::new(device).map_err(Pn532Error::transport)?;
+        Ok(I2cTransport { i2c, address })
+    }
+}
+
+impl Transport for I2cTransport {
+    fn wake_up(&mut self) -> Result<(), Pn532Error> {
+        // Совпадение адреса будит чип из PowerDown, первое обращение он может
+        // не подтвердить – ошибку игнорируем
+        let mut buffer = [0u8; 1];
+        let _ = self.i2c.read(self.address, &mut buffer);
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        Ok(())
+    }
+
+    fn wait_ready(&mut self, timeout_ms: u32) -> Result<bool, Pn532Error> {
+        let start = std::time::Instant::now();
+
+        loop {
+            let mut buffer = [0u8; 1];
+            if self.i2c.read(self.address, &mut buffer).is_ok() {
+                // "Bit 0 of the status byte indicates if the PN532 is ready to be read (1: ready, 0: not ready)."
+                if buffer[0] & 0x01 == 0x01 {
+                    return Ok(true);
+                }
+            }
+
+            if start.elapsed().as_millis() > timeout_ms as u128 {
+                return Ok(false);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), Pn532Error> {
+        match self.i2c.write(self.address, frame) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                debug!(error = ?e, "I2C write error");
+                Err(Pn532Error::transport(e))
+            }
+        }
+    }
+
+    fn read_frame(&mut self, len: usize) -> Result<Vec<u8>, Pn532Error> {
+        // По I2C каждое чтение начинается со статусного байта
+        let mut buffer = vec![0u8; len + 1];
+        self.i2c
+            .read(self.address, &mut buffer)
+            .map_err(Pn532Error::transport)?;
+        buffer.remove(0);
+        Ok(buffer)
+    }
+}
@@ -0,0 +1,124 @@
+use super::Transport;
+use crate::pn532reader::error::Pn532Error;
+use gpiocdev::line::{EdgeDetection, Value};
+use gpiocdev::Request;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Вывод IRQ PN532: чип опускает его в 0, когда ACK или ответ готов к чтению,
+/// и поднимает, когда хост их забрал.
+pub struct IrqLine {
+    request: Request,
+    offset: u32,
+}
+
+impl IrqLine {
+    /// `chip` – `/dev/gpiochip0` или просто `gpiochip0`, `line` – номер линии на чипе.
+    pub fn new(chip: &str, line: u32) -> Result<Self, Pn532Error> {
+        let path = if chip.starts_with('/') {
+            chip.to_string()
+        } else {
+            format!("/dev/{}", chip)
+        };
+        let request = Request::builder()
+            .on_chip(path)
+            .with_consumer("nfc_reader")
+            .with_line(line)
+            .as_input()
+            .with_edge_detection(EdgeDetection::FallingEdge)
+            .request()
+            .map_err(Pn532Error::transport)?;
+        Ok(IrqLine {
+            request,
+            offset: line,
+        })
+    }
+
+    fn is_low(&self) -> Result<bool, Pn532Error> {
+        let value = self
+            .request
+            .value(self.offset)
+            .map_err(Pn532Error::transport)?;
+        Ok(value == Value::Inactive)
+    }
+
+    /// Ждёт низкого уровня не дольше `timeout`. Фронты, накопившиеся с прошлого
+    /// ожидания, выбрасываются, а уровень проверяется до ожидания фронта –
+    /// так не теряется IRQ, опустившийся раньше, чем мы начали ждать.
+    pub fn wait_low(&mut self, timeout: Duration) -> Result<bool, Pn532Error> {
+        while self
+            .request
+            .has_edge_event()
+            .map_err(Pn532Error::transport)?
+        {
+            self.request
+                .read_edge_event()
+                .map_err(Pn532Error::transport)?;
+        }
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.is_low()? {
+                return Ok(true);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero()
+                || !self
+                    .request
+                    .wait_edge_event(remaining)
+                    .map_err(Pn532Error::transport)?
+            {
+                return Ok(false);
+            }
+            self.request
+                .read_edge_event()
+                .map_err(Pn532Error::transport)?;
+        }
+    }
+}
+
+/// Любой транспорт, но готовность ждём по IRQ, а не опросом шины. Если GPIO
+/// перестал работать, дальше используется опрос вложенного транспорта.
+pub struct IrqTransport {
+    inner: Box<dyn Transport>,
+    irq: Option<IrqLine>,
+}
+
+impl IrqTransport {
+    pub fn new(inner: Box<dyn Transport>, irq: IrqLine) -> Self {
+        IrqTransport {
+            inner,
+            irq: Some(irq),
+        }
+    }
+}
+
+impl Transport for IrqTransport {
+    fn wake_up(&mut self) -> Result<(), Pn532Error> {
+        self.inner.wake_up()
+    }
+
+    fn wait_ready(&mut self, timeout_ms: u32) -> Result<bool, Pn532Error> {
+        if let Some(irq) = self.irq.as_mut() {
+            match irq.wait_low(Duration::from_millis(timeout_ms as u64)) {
+                Ok(ready) => return Ok(ready),
+                Err(e) => {
+                    warn!(error = %e, "IRQ line failed, falling back to polling");
+                    self.irq = None;
+                }
+            }
+        }
+        self.inner.wait_ready(timeout_ms)
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), Pn532Error> {
+        self.inner.write_frame(frame)
+    }
+
+    fn read_frame(&mut self, len: usize) -> Result<Vec<u8>, Pn532Error> {
+        self.inner.read_frame(len)
+    }
+
+    fn uses_irq(&self) -> bool {
+        self.irq.is_some()
+    }
+}
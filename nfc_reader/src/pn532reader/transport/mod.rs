@@ -0,0 +1,36 @@
+pub mod hsu;
+pub mod i2c;
+pub mod irq;
+pub mod spi;
+
+pub use hsu::HsuTransport;
+pub use i2c::I2cTransport;
+pub use irq::{IrqLine, IrqTransport};
+pub use spi::SpiTransport;
+
+use crate::pn532reader::error::Pn532Error;
+
+/// Физический интерфейс до PN532. Фрейминг (LEN/LCS/DCS, ACK) общий и живёт
+/// в `commands.rs`/`response.rs`; транспорт отвечает только за служебные байты
+/// конкретной шины и за ожидание готовности чипа.
+pub trait Transport {
+    /// Выводит чип из Power Down, если шине для этого нужно что-то особенное.
+    fn wake_up(&mut self) -> Result<(), Pn532Error> {
+        Ok(())
+    }
+
+    /// Ждёт, пока PN532 подготовит данные для чтения.
+    fn wait_ready(&mut self, timeout_ms: u32) -> Result<bool, Pn532Error>;
+
+    /// Отправляет готовый фрейм `PRE SC1 SC2 LEN LCS TFI DATA DCS POST`.
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), Pn532Error>;
+
+    /// Читает до `len` байт фрейма, начиная с преамбулы, без служебных байт шины.
+    /// Потоковые шины (HSU) возвращают ровно один фрейм, даже если он короче.
+    fn read_frame(&mut self, len: usize) -> Result<Vec<u8>, Pn532Error>;
+
+    /// Готовность приходит по IRQ, а не опросом шины – ждать можно без пауз.
+    fn uses_irq(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,89 @@
+use super::Transport;
+use crate::pn532reader::error::Pn532Error;
+use spidev::{SpiModeFlags, Spidev, SpidevOptions, SpidevTransfer};
+
+// Первый байт каждой SPI-транзакции – что хост собирается делать
+const SPI_DATA_WRITE: u8 = 0x01;
+const SPI_STATUS_READ: u8 = 0x02;
+const SPI_DATA_READ: u8 = 0x03;
+
+/// PN532 по SPI передаёт байты младшим битом вперёд. Большинство SPI-контроллеров
+/// (в том числе на Orange Pi) SPI_LSB_FIRST не умеют, поэтому по умолчанию
+/// биты разворачиваются программно, как в `msb-spi` у крейта pn532.
+pub struct SpiTransport {
+    spi: Spidev,
+    reverse_bits: bool,
+}
+
+impl SpiTransport {
+    pub fn new(device: &str, speed_hz: u32, hw_lsb_first: bool) -> Result<Self, Pn532Error> {
+        let mut spi = Spidev::open(device)?;
+        let options = SpidevOptions::new()
+            .bits_per_word(8)
+            .max_speed_hz(speed_hz)
+            .lsb_first(hw_lsb_first)
+            .mode(SpiModeFlags::SPI_MODE_0)
+            .build();
+        spi.configure(&options)?;
+        Ok(SpiTransport {
+            spi,
+            reverse_bits: !hw_lsb_first,
+        })
+    }
+
+    fn encode(&self, byte: u8) -> u8 {
+        if self.reverse_bits {
+            byte.reverse_bits()
+        } else {
+            byte
+        }
+    }
+
+    /// Префикс и данные в одной транзакции – CS держится всё время.
+    fn read_with_prefix(&mut self, prefix: u8, len: usize) -> Result<Vec<u8>, Pn532Error> {
+        let tx = [self.encode(prefix)];
+        let mut rx = vec![0u8; len];
+        {
+            let mut transfers = [SpidevTransfer::write(&tx), SpidevTransfer::read(&mut rx)];
+            self.spi.transfer_multiple(&mut transfers)?;
+        }
+        Ok(rx.into_iter().map(|b| self.encode(b)).collect())
+    }
+}
+
+impl Transport for SpiTransport {
+    fn wake_up(&mut self) -> Result<(), Pn532Error> {
+        // Спад NSS будит чип из PowerDown; ответ на такой запрос не нужен
+        let _ = self.read_with_prefix(SPI_STATUS_READ, 1);
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        Ok(())
+    }
+
+    fn wait_ready(&mut self, timeout_ms: u32) -> Result<bool, Pn532Error> {
+        let start = std::time::Instant::now();
+
+        loop {
+            let status = self.read_with_prefix(SPI_STATUS_READ, 1)?;
+            if status[0] & 0x01 == 0x01 {
+                return Ok(true);
+            }
+
+            if start.elapsed().as_millis() > timeout_ms as u128 {
+                return Ok(false);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), Pn532Error> {
+        let mut tx = Vec::with_capacity(frame.len() + 1);
+        tx.push(self.encode(SPI_DATA_WRITE));
+        tx.extend(frame.iter().map(|&b| self.encode(b)));
+        self.spi.transfer(&mut SpidevTransfer::write(&tx))?;
+        Ok(())
+    }
+
+    fn read_frame(&mut self, len: usize) -> Result<Vec<u8>, Pn532Error> {
+        self.read_with_prefix(SPI_DATA_READ, len)
+    }
+}
@@ -0,0 +1,141 @@
+use crate::feedback::Outcome;
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Сколько секунд без успешного опроса станция считается живой.
+pub const DEFAULT_MAX_POLL_AGE: u64 = 10;
+
+/// Что `/status` знает о работающем считывателе.
+#[derive(Debug)]
+struct Station {
+    firmware: Option<String>,
+    last_poll: Option<SystemTime>,
+    last_card: Option<(SystemTime, Outcome)>,
+}
+
+static STATION: Mutex<Station> = Mutex::new(Station {
+    firmware: None,
+    last_poll: None,
+    last_card: None,
+});
+
+/// После GetFirmwareVersion и SAMConfiguration.
+pub fn set_firmware(version: u32) {
+    let firmware = format!(
+        "PN5{:02X} {}.{}",
+        (version >> 16) & 0xFF,
+        (version >> 8) & 0xFF,
+        version & 0xFF
+    );
+    STATION.lock().unwrap().firmware = Some(firmware);
+}
+
+/// Опрос поля или проверка карты прошли без ошибки шины.
+pub fn poll_succeeded() {
+    STATION.lock().unwrap().last_poll = Some(SystemTime::now());
+}
+
+pub fn card_processed(outcome: Outcome) {
+    STATION.lock().unwrap().last_card = Some((SystemTime::now(), outcome));
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn age_seconds(time: SystemTime) -> f64 {
+    SystemTime::now()
+        .duration_since(time)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// Ответ `/status`: исправна ли станция и JSON с подробностями. Исправна –
+/// прошивка прочитана и последний успешный опрос не старше `max_poll_age`.
+pub fn report(max_poll_age: Duration) -> (bool, Value) {
+    let station = STATION.lock().unwrap();
+    let healthy = station.firmware.is_some()
+        && station
+            .last_poll
+            .is_some_and(|at| age_seconds(at) <= max_poll_age.as_secs_f64());
+    let body = json!({
+        "healthy": healthy,
+        "firmware": station.firmware,
+        "last_poll": station.last_poll.map(unix_seconds),
+        "last_poll_age_s": station.last_poll.map(age_seconds),
+        "last_card": station.last_card.map(|(at, _)| unix_seconds(at)),
+        "last_outcome": station.last_card.map(|(_, outcome)| outcome.as_str()),
+    });
+    (healthy, body)
+}
+
+/// `max_poll_age=N` из строки запроса `/status?max_poll_age=N`.
+pub fn max_poll_age(url: &str) -> Duration {
+    let seconds = url
+        .split_once('?')
+        .and_then(|(_, query)| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("max_poll_age="))
+        })
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_POLL_AGE);
+    Duration::from_secs(seconds)
+}
+
+/// Спрашивает работающий считыватель: код HTTP и тело ответа `/status`.
+pub fn query(addr: &str, max_poll_age: u64) -> io::Result<(u16, String)> {
+    let mut stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    write!(
+        stream,
+        "GET /status?max_poll_age={} HTTP/1.0\r\nHost: {}\r\n\r\n",
+        max_poll_age, addr
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let code = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not an HTTP response"))?;
+    Ok((code, body.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_station_health() {
+        assert_eq!(max_poll_age("/status"), Duration::from_secs(10));
+        assert_eq!(
+            max_poll_age("/status?max_poll_age=3"),
+            Duration::from_secs(3)
+        );
+
+        set_firmware(0x0032_0106);
+        poll_succeeded();
+        card_processed(Outcome::ReadOk);
+        let (healthy, body) = report(Duration::from_secs(10));
+        assert!(healthy);
+        assert_eq!(body["firmware"], "PN532 1.6");
+        assert_eq!(body["last_outcome"], "read-ok");
+
+        let addr = crate::metrics::serve("127.0.0.1:0").unwrap().to_string();
+        let (code, body) = query(&addr, 10).unwrap();
+        assert_eq!(code, 200);
+        assert!(body.contains("\"healthy\":true"));
+
+        STATION.lock().unwrap().last_poll = Some(SystemTime::now() - Duration::from_secs(30));
+        assert!(!report(Duration::from_secs(10)).0);
+        assert_eq!(query(&addr, 10).unwrap().0, 503);
+        assert_eq!(query(&addr, 60).unwrap().0, 200);
+    }
+}
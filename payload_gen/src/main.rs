@@ -1,5 +1,7 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use crc32fast::Hasher;
+use nfc_format::layout;
+use nfc_format::ndef::{CTF_MIME_TYPE, Message, Record, wrap_tlv};
 use std::fs::File;
 use std::io::Write;
 
@@ -11,6 +13,16 @@ const NOP: u8 = 0x00;
 
 const TAG: u8 = 0x03;
 
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// [tag][encrypted payload][CRC] as griphd reads it
+    Raw,
+    /// NDEF TLV with an application/x-ctf MIME record, ready to be written to a tag
+    Ndef,
+    /// GRPH header block + payload, for `nfc_reader write` without NDEF
+    Card,
+}
+
 #[derive(Parser)]
 struct Args {
     #[arg(short = 'i', long)]
@@ -19,12 +31,19 @@ struct Args {
     call_addr: usize,
     #[arg(short = 'k', long, default_value = "0x5B")]
     xkey: String,
+    #[arg(short = 'o', long, default_value = "/tmp/rfid_input.bin")]
+    output: String,
+    #[arg(short = 'f', long, value_enum, default_value = "raw")]
+    format: Format,
+    /// Split the NDEF record into chunks of this many bytes
+    #[arg(long)]
+    ndef_chunk: Option<usize>,
 }
 
 fn main() -> std::io::Result<()> {
     let args = Args::parse();
     let tokens = args.instruction.split_whitespace().collect::<Vec<_>>();
-    let xkey = u8::from_str_radix(&args.xkey.trim_start_matches("0x"), 16).unwrap();
+    let xkey = u8::from_str_radix(args.xkey.trim_start_matches("0x"), 16).unwrap();
     let mut program: Vec<u8> = Vec::new();
     let mut i = 0;
     println!("{:?}", tokens);
@@ -94,8 +113,20 @@ fn main() -> std::io::Result<()> {
     let mut f_payload = vec![TAG];
     f_payload.extend(&encrypted);
     f_payload.extend(&crc_byte);
-    let mut file = File::create("/tmp/rfid_input.bin")?;
-    file.write_all(&f_payload)?;
+    let output = match args.format {
+        Format::Raw => f_payload,
+        Format::Ndef => {
+            let message = Message::new(vec![Record::mime(CTF_MIME_TYPE, &f_payload)]);
+            message
+                .to_bytes_chunked(args.ndef_chunk)
+                .and_then(|bytes| wrap_tlv(&bytes))
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?
+        }
+        Format::Card => layout::encode(&f_payload)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+    };
+    let mut file = File::create(&args.output)?;
+    file.write_all(&output)?;
 
     Ok(())
 }